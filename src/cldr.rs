@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const CLDR_DIR: &str = "cldr";
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// CLDR short name and keywords for a character or sequence
+#[derive(Default)]
+pub struct Annotation {
+  pub tts: Option<String>,
+  pub keywords: Vec<String>,
+}
+
+/// Annotation files for a locale, such as `cldr/annotations/en.xml`
+pub fn annotation_paths(locale: &str) -> Vec<PathBuf> {
+  ["annotations", "annotationsDerived"]
+    .iter()
+    .map(|dir| {
+      Path::new(CLDR_DIR)
+        .join(dir)
+        .join(format!("{}.xml", locale))
+    })
+    .collect()
+}
+
+/// Lists the locales to search besides the default one, in order of preference
+pub const LOCALES_FILE: &str = "locales.txt";
+
+/// The locales in `locales.txt` that have an annotations file, such as `de`
+/// for `cldr/annotations/de.xml`. The names of the first one are shown with
+/// the results.
+pub fn locales() -> Vec<String> {
+  let text = fs::read_to_string(LOCALES_FILE).unwrap_or_default();
+  let mut locales: Vec<String> = vec![];
+  for line in text.lines() {
+    let line = line.split('#').next().unwrap_or("");
+    for locale in line.split(|c: char| c == ',' || c.is_whitespace()) {
+      if locale.is_empty() || locale == DEFAULT_LOCALE || locales.iter().any(|l| l == locale) {
+        continue;
+      }
+      if annotation_paths(locale)[0].exists() {
+        locales.push(locale.to_string());
+      }
+    }
+  }
+  locales
+}
+
+fn unescape(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(i) = rest.find('&') {
+    out.push_str(&rest[..i]);
+    rest = &rest[i..];
+    let end = match rest.find(';') {
+      Some(end) => end,
+      None => break,
+    };
+    let entity = &rest[1..end];
+    let decoded = match entity {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ => entity
+        .strip_prefix("#x")
+        .map(|hex| u32::from_str_radix(hex, 16))
+        .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+        .and_then(|cp| cp.ok())
+        .and_then(char::from_u32),
+    };
+    match decoded {
+      Some(c) => {
+        out.push(c);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+  let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+  let len = tag[start..].find('"')?;
+  Some(&tag[start..start + len])
+}
+
+/// Reads the `<annotation>` elements of a CLDR annotations file, keyed by character
+pub fn read_annotations<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, Annotation>> {
+  let xml = fs::read_to_string(path)?;
+  let mut annotations: HashMap<String, Annotation> = HashMap::default();
+  let mut rest = xml.as_str();
+  while let Some(start) = rest.find("<annotation ") {
+    rest = &rest[start..];
+    let (tag_end, end) = match (rest.find('>'), rest.find("</annotation>")) {
+      (Some(tag_end), Some(end)) if tag_end < end => (tag_end, end),
+      _ => break,
+    };
+    let tag = &rest[..tag_end];
+    let text = unescape(rest[tag_end + 1..end].trim());
+    rest = &rest[end..];
+
+    let cp = match attribute(tag, "cp") {
+      Some(cp) => unescape(cp),
+      None => continue,
+    };
+    let annotation = annotations.entry(cp).or_default();
+    if attribute(tag, "type") == Some("tts") {
+      annotation.tts = Some(text);
+    } else {
+      annotation.keywords.extend(
+        text
+          .split('|')
+          .map(|k| k.trim().to_string())
+          .filter(|k| !k.is_empty()),
+      );
+    }
+  }
+  Ok(annotations)
+}
+
+/// The annotation of a character or sequence. CLDR leaves out the emoji
+/// presentation selector.
+pub fn find<'a>(
+  annotations: &'a HashMap<String, Annotation>,
+  chars: &str,
+) -> Option<&'a Annotation> {
+  annotations
+    .get(chars)
+    .or_else(|| annotations.get(&chars.replace('\u{FE0F}', "")))
+}
+
+/// All annotations for a locale from the files that are present
+pub fn load_annotations(locale: &str) -> io::Result<HashMap<String, Annotation>> {
+  let mut annotations: HashMap<String, Annotation> = HashMap::default();
+  for path in annotation_paths(locale) {
+    if !path.exists() {
+      continue;
+    }
+    for (cp, a) in read_annotations(path)? {
+      let merged = annotations.entry(cp).or_default();
+      if merged.tts.is_none() {
+        merged.tts = a.tts;
+      }
+      merged.keywords.extend(a.keywords);
+    }
+  }
+  Ok(annotations)
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// A copy of the system Compose file, e.g. `/usr/share/X11/locale/en_US.UTF-8/Compose`
+pub const COMPOSE_FILE: &str = "Compose";
+
+/// The system Compose file next to the characters file, then the user's own
+/// file as found by X11: `$XCOMPOSEFILE` or `~/.XCompose`
+pub fn compose_files() -> Vec<PathBuf> {
+  let mut files = vec![PathBuf::from(COMPOSE_FILE)];
+  if let Some(path) = env::var_os("XCOMPOSEFILE") {
+    files.push(PathBuf::from(path));
+  } else if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+    files.push(Path::new(&home).join(".XCompose"));
+  }
+  files
+}
+
+/// The character typed by a keysym, for the keysyms found in compose sequences
+fn keysym_char(name: &str) -> Option<char> {
+  let mut chars = name.chars();
+  if let (Some(c), None) = (chars.next(), chars.next()) {
+    return Some(c);
+  }
+  if let Some(hex) = name.strip_prefix('U') {
+    return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+  }
+  Some(match name {
+    "space" => ' ',
+    "exclam" => '!',
+    "quotedbl" => '"',
+    "numbersign" => '#',
+    "dollar" => '$',
+    "percent" => '%',
+    "ampersand" => '&',
+    "apostrophe" => '\'',
+    "parenleft" => '(',
+    "parenright" => ')',
+    "asterisk" => '*',
+    "plus" => '+',
+    "comma" => ',',
+    "minus" => '-',
+    "period" => '.',
+    "slash" => '/',
+    "colon" => ':',
+    "semicolon" => ';',
+    "less" => '<',
+    "equal" => '=',
+    "greater" => '>',
+    "question" => '?',
+    "at" => '@',
+    "bracketleft" => '[',
+    "backslash" => '\\',
+    "bracketright" => ']',
+    "asciicircum" => '^',
+    "underscore" => '_',
+    "grave" => '`',
+    "braceleft" => '{',
+    "bar" => '|',
+    "braceright" => '}',
+    "asciitilde" => '~',
+    _ => return None,
+  })
+}
+
+/// Reads a quoted string, returning it and the text after it
+fn read_string(s: &str) -> Option<(String, &str)> {
+  let mut out = String::new();
+  let mut chars = s.strip_prefix('"')?.char_indices();
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' => return Some((out, &s[i + 2..])),
+      '\\' => match chars.next()?.1 {
+        'n' => out.push('\n'),
+        c => out.push(c),
+      },
+      c => out.push(c),
+    }
+  }
+  None
+}
+
+/// Parses a line such as `<Multi_key> <o> <c> : "©" copyright`, returning
+/// the keys typed after the compose key and the resulting text
+fn parse_line(line: &str) -> Option<(String, String)> {
+  let mut rest = line.trim_start();
+  let mut keys = vec![];
+  while let Some(r) = rest.strip_prefix('<') {
+    let end = r.find('>')?;
+    keys.push(&r[..end]);
+    rest = r[end + 1..].trim_start();
+  }
+  let (text, _) = read_string(rest.strip_prefix(':')?.trim_start())?;
+  // Sequences that start with a dead key cannot be typed by name
+  match keys.split_first() {
+    Some((&"Multi_key", keys)) if !keys.is_empty() => {
+      let sequence = keys
+        .iter()
+        .map(|k| keysym_char(k))
+        .collect::<Option<String>>()?;
+      Some((sequence, text))
+    }
+    _ => None,
+  }
+}
+
+/// Compose key sequences keyed by the text they produce
+pub fn read_compose<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, Vec<String>>> {
+  let file = File::open(path)?;
+  let mut sequences: HashMap<String, Vec<String>> = HashMap::default();
+  for line in io::BufReader::new(file).lines().flatten() {
+    if let Some((sequence, text)) = parse_line(&line) {
+      let list = sequences.entry(text).or_default();
+      if !list.contains(&sequence) {
+        list.push(sequence);
+      }
+    }
+  }
+  Ok(sequences)
+}
@@ -0,0 +1,96 @@
+use crate::ucd;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+pub const DIGRAPHS_FILE: &str = "digraphs.txt";
+
+/// The code point column: `03b1` in RFC 1345, and `03B1` or `0xe4` in Vim
+fn parse_hex(token: &str) -> Option<char> {
+  let digits = match token.strip_prefix("0x") {
+    Some(digits) if digits.len() == 2 => digits,
+    Some(_) => return None,
+    None if (4..=6).contains(&token.len()) => token,
+    None => return None,
+  };
+  ucd::parse_cp(digits).and_then(char::from_u32)
+}
+
+/// A character name as RFC 1345 writes it, in upper case
+fn is_name(word: &str) -> bool {
+  word.starts_with(|c: char| c.is_ascii_uppercase()) && !word.chars().any(char::is_lowercase)
+}
+
+/// The mnemonic and character of a line in the format of Vim's
+/// `:help digraph-table` (`α  a*  03B1  0945  GREEK SMALL LETTER ALPHA`) or
+/// RFC 1345 (`a*  03b1  GREEK SMALL LETTER ALPHA`). Other lines, such as the
+/// text around the tables, give `None`.
+fn parse_digraph(line: &str) -> Option<(&str, char)> {
+  let tokens: Vec<&str> = line.split_whitespace().collect();
+  // Vim lists the character itself first
+  if let [shown, mnemonic, hex, ..] = tokens[..] {
+    match parse_hex(hex) {
+      Some(c) if shown.chars().eq(Some(c)) => return Some((mnemonic, c)),
+      _ => {}
+    }
+  }
+  match tokens[..] {
+    [mnemonic, hex, name, ..] if is_name(name) => parse_hex(hex).map(|c| (mnemonic, c)),
+    _ => None,
+  }
+}
+
+/// Reads the mnemonics of a file with lines in the format of Vim's digraph
+/// table or RFC 1345, keyed by character
+pub fn read_digraphs<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, Vec<String>>> {
+  let file = File::open(path)?;
+  let mut digraphs: HashMap<String, Vec<String>> = HashMap::default();
+  for line in io::BufReader::new(file).lines().flatten() {
+    if let Some((mnemonic, c)) = parse_digraph(&line) {
+      let list = digraphs.entry(c.to_string()).or_default();
+      if !list.iter().any(|m| m == mnemonic) {
+        list.push(mnemonic.to_string());
+      }
+    }
+  }
+  Ok(digraphs)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_vim_and_rfc_1345_lines() {
+    assert_eq!(
+      parse_digraph("α\ta*\t03B1\t0945\tGREEK SMALL LETTER ALPHA"),
+      Some(("a*", 'α'))
+    );
+    assert_eq!(
+      parse_digraph("ä\ta:\t0xe4\t228\tLATIN SMALL LETTER A WITH DIAERESIS"),
+      Some(("a:", 'ä'))
+    );
+    assert_eq!(
+      parse_digraph(" a*     03b1    GREEK SMALL LETTER ALPHA"),
+      Some(("a*", 'α'))
+    );
+    assert_eq!(
+      parse_digraph(" ->     2192    RIGHTWARDS ARROW"),
+      Some(("->", '→'))
+    );
+  }
+
+  #[test]
+  fn skips_text_around_the_tables() {
+    for line in [
+      "   ISO 10646 is the standard that the mnemonics refer to.",
+      "   character set ISO 10646 and the mnemonics",
+      "RFC 1345            Character Mnemonics & Character Sets           June 1992",
+      "^@\tNU\t0x00\t  0\tNULL (NUL)",
+      "char  digraph\thex\tdec\tofficial name ~",
+    ] {
+      assert_eq!(parse_digraph(line), None, "{}", line);
+    }
+  }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const ENTITIES_FILE: &str = "entities.json";
+
+/// The name of an entity as typed with or without `&` and `;`, e.g. `rarr` for `&rarr;`
+pub fn entity_name(s: &str) -> &str {
+  let s = s.trim();
+  let s = s.strip_prefix('&').unwrap_or(s);
+  s.strip_suffix(';').unwrap_or(s)
+}
+
+/// Reads the WHATWG named character references (https://html.spec.whatwg.org/entities.json),
+/// keyed by the character or sequence they stand for
+pub fn read_entities<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, Vec<String>>> {
+  let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+  let mut entities: HashMap<String, Vec<String>> = HashMap::default();
+  for (key, value) in json.as_object().into_iter().flatten() {
+    let chars = match value.get("characters").and_then(|c| c.as_str()) {
+      Some(chars) => chars,
+      None => continue,
+    };
+    // Some entities are listed both with and without the semicolon
+    let name = entity_name(key);
+    let names = entities.entry(chars.to_string()).or_default();
+    if !names.iter().any(|n| n == name) {
+      names.push(name.to_string());
+    }
+  }
+  Ok(entities)
+}
@@ -0,0 +1,73 @@
+use crate::search;
+use crate::storage;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+const FAVORITES_FILE: &str = "favorites.txt";
+
+/// Characters pinned by the user. The file has the same format as the
+/// characters file and can be edited by hand.
+#[derive(Clone, Default)]
+pub struct Favorites {
+  lines: Vec<String>,
+}
+
+fn line_chars(line: &str) -> Option<String> {
+  search::parse_line(line).map(|entry| entry.chars)
+}
+
+impl Favorites {
+  fn path() -> PathBuf {
+    storage::data_dir().join(FAVORITES_FILE)
+  }
+
+  pub fn load() -> Favorites {
+    let lines = match File::open(Self::path()) {
+      Ok(file) => io::BufReader::new(file).lines().flatten().collect(),
+      Err(_) => vec![],
+    };
+    Favorites { lines }
+  }
+
+  fn save(&self) -> io::Result<()> {
+    fs::create_dir_all(storage::data_dir())?;
+    let mut file = File::create(Self::path())?;
+    for line in &self.lines {
+      writeln!(file, "{}", line)?;
+    }
+    Ok(())
+  }
+
+  pub fn chars(&self) -> impl Iterator<Item = String> + '_ {
+    self.lines.iter().flat_map(|line| line_chars(line))
+  }
+
+  pub fn contains(&self, chars: &str) -> bool {
+    self
+      .lines
+      .iter()
+      .any(|line| line_chars(line).as_deref() == Some(chars))
+  }
+
+  /// Adds or removes a favorite, returning whether it is now a favorite
+  pub fn toggle(&mut self, chars: &str, name: &str) -> io::Result<bool> {
+    let added = if self.contains(chars) {
+      self
+        .lines
+        .retain(|line| line_chars(line).as_deref() != Some(chars));
+      false
+    } else {
+      let entry = search::Entry {
+        chars: chars.to_string(),
+        name: name.to_string(),
+        keywords: vec![],
+        props: vec![],
+      };
+      self.lines.push(search::format_line(&entry));
+      true
+    };
+    self.save()?;
+    Ok(added)
+  }
+}
@@ -0,0 +1,84 @@
+use crate::storage;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "history.txt";
+
+/// A use this long ago counts half as much as a use now
+const HALF_LIFE_SECS: f64 = 14. * 24. * 60. * 60.;
+
+/// The file is trimmed to the most recent uses once it grows past this
+const MAX_USES: usize = 5000;
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+/// Every time a character was typed by the picker, stored as lines of
+/// `<unix time>\t<characters>`
+#[derive(Clone, Default)]
+pub struct History {
+  uses: Vec<(u64, String)>,
+}
+
+impl History {
+  fn path() -> PathBuf {
+    storage::data_dir().join(HISTORY_FILE)
+  }
+
+  pub fn load() -> History {
+    let mut uses = vec![];
+    if let Ok(file) = File::open(Self::path()) {
+      for line in io::BufReader::new(file).lines().flatten() {
+        if let Some((time, chars)) = line.split_once('\t') {
+          if let Ok(time) = time.parse() {
+            uses.push((time, chars.to_string()));
+          }
+        }
+      }
+    }
+    let mut history = History { uses };
+    if history.uses.len() > MAX_USES {
+      history.uses.drain(..history.uses.len() - MAX_USES);
+      let _ = history.save();
+    }
+    history
+  }
+
+  fn save(&self) -> io::Result<()> {
+    fs::create_dir_all(storage::data_dir())?;
+    let mut file = File::create(Self::path())?;
+    for (time, chars) in &self.uses {
+      writeln!(file, "{}\t{}", time, chars)?;
+    }
+    Ok(())
+  }
+
+  pub fn record(&mut self, chars: &str) -> io::Result<()> {
+    let time = now_secs();
+    self.uses.push((time, chars.to_string()));
+    fs::create_dir_all(storage::data_dir())?;
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(Self::path())?;
+    writeln!(file, "{}\t{}", time, chars)
+  }
+
+  /// How often each character has been used, with recent uses counting more
+  pub fn scores(&self) -> HashMap<&str, f32> {
+    let now = now_secs();
+    let mut scores = HashMap::default();
+    for (time, chars) in &self.uses {
+      let age = now.saturating_sub(*time) as f64;
+      *scores.entry(chars.as_str()).or_insert(0.) += 0.5f64.powf(age / HALF_LIFE_SECS) as f32;
+    }
+    scores
+  }
+}
@@ -0,0 +1,138 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Format characters that take up no space and would otherwise not be seen
+const INVISIBLE_RANGES: [(u32, u32); 10] = [
+  (0x00AD, 0x00AD),
+  (0x061C, 0x061C),
+  (0x180E, 0x180E),
+  (0x200B, 0x200F),
+  (0x202A, 0x202E),
+  (0x2060, 0x2064),
+  (0x2066, 0x206F),
+  (0xFE00, 0xFE0F),
+  (0xFEFF, 0xFEFF),
+  (0xE0000, 0xE007F),
+];
+
+fn is_invisible(c: char) -> bool {
+  c.is_whitespace()
+    || c.is_control()
+    || INVISIBLE_RANGES
+      .iter()
+      .any(|&(first, last)| first <= c as u32 && c as u32 <= last)
+}
+
+/// Latin letters outside ASCII, such as `é` in `café`
+fn is_latin(c: char) -> bool {
+  c.is_ascii() || ('\u{C0}'..='\u{24F}').contains(&c) || ('\u{1E00}'..='\u{1EFF}').contains(&c)
+}
+
+/// A word with ASCII letters and letters of another script, as in `раypal`
+/// where the first two letters are Cyrillic
+fn mixes_scripts(word: &str) -> bool {
+  word.chars().any(|c| c.is_ascii_alphabetic())
+    && word.chars().any(|c| c.is_alphabetic() && !is_latin(c))
+}
+
+/// Whether the search text is better inspected than searched for: it has
+/// invisible characters, symbols or punctuation outside ASCII, such as smart
+/// quotes, or a word that hides lookalikes of ASCII letters. Letters with
+/// their combining marks, as in `ซ้าย` or `नमस्ते`, are searched for.
+pub fn should_inspect(s: &str) -> bool {
+  s.split(' ').any(|word| {
+    mixes_scripts(word)
+      || word.graphemes(true).any(|g| {
+        g.chars().any(is_invisible) || !(g.is_ascii() || g.starts_with(char::is_alphanumeric))
+      })
+  })
+}
+
+/// The grapheme clusters of the text, which are shown one per cell
+pub fn graphemes(s: &str) -> impl Iterator<Item = &str> {
+  s.graphemes(true)
+}
+
+/// Text to show in a cell, with a visible stand-in for characters that
+/// would otherwise be blank, such as `␣` for a space
+pub fn visible(chars: &str) -> String {
+  if chars.is_empty() || !chars.chars().all(is_invisible) {
+    return chars.to_string();
+  }
+  chars
+    .chars()
+    .map(|c| match c as u32 {
+      cp @ 0..=0x1F => char::from_u32(0x2400 + cp).unwrap(),
+      0x20 => '␣',
+      0x7F => '␡',
+      _ => '⬚',
+    })
+    .collect()
+}
+
+/// A line about one code point: `U+2013 EN DASH (Pd) UTF-8 E2 80 93 UTF-16 2013`
+pub fn describe_char(c: char, name: &str, gc: &str) -> String {
+  let mut utf8 = [0; 4];
+  let utf8: Vec<String> = c
+    .encode_utf8(&mut utf8)
+    .bytes()
+    .map(|b| format!("{:02X}", b))
+    .collect();
+  let mut utf16 = [0; 2];
+  let utf16: Vec<String> = c
+    .encode_utf16(&mut utf16)
+    .iter()
+    .map(|u| format!("{:04X}", u))
+    .collect();
+  let gc = if gc.is_empty() { "?" } else { gc };
+  format!(
+    "U+{:04X} {} ({}) UTF-8 {} UTF-16 {}",
+    c as u32,
+    name,
+    gc,
+    utf8.join(" "),
+    utf16.join(" ")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn inspects_symbols_and_invisible_characters() {
+    for s in [
+      "a–b",
+      "“quoted”",
+      "no\u{a0}break",
+      "\u{200b}",
+      "\u{301}",
+      "★",
+    ] {
+      assert!(should_inspect(s), "{}", s);
+    }
+  }
+
+  #[test]
+  fn inspects_words_with_lookalike_letters() {
+    // Cyrillic `р` and `а`, and Greek `Ι`
+    for s in ["раypal", "log in to раypal", "Ιnbox"] {
+      assert!(should_inspect(s), "{}", s);
+    }
+  }
+
+  #[test]
+  fn searches_for_words_in_any_script() {
+    for s in [
+      "greek cap",
+      "->",
+      "a:",
+      "café",
+      "ซ้าย",
+      "नमस्ते",
+      "Ελλάδα",
+      "日本",
+    ] {
+      assert!(!should_inspect(s), "{}", s);
+    }
+  }
+}
@@ -0,0 +1,290 @@
+use crate::search::{self, Entry};
+use crate::sets;
+use crate::storage;
+use crate::ucd;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+/// Folder of files that add to and override the characters file
+pub const OVERRIDES_DIR: &str = "characters.d";
+
+fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+where
+  P: AsRef<Path>,
+{
+  let file = File::open(filename)?;
+  Ok(io::BufReader::new(file).lines())
+}
+
+/// The characters file, then the `.txt` files and sets in `characters.d` next to it,
+/// then those in `characters.d` in the data folder, each folder in name order.
+/// Later files override earlier ones.
+pub fn layer_files() -> Vec<PathBuf> {
+  let mut files = vec![PathBuf::from(search::CHAR_FILE)];
+  for dir in [
+    PathBuf::from(OVERRIDES_DIR),
+    storage::data_dir().join(OVERRIDES_DIR),
+  ] {
+    let mut overrides: Vec<PathBuf> = fs::read_dir(&dir)
+      .into_iter()
+      .flatten()
+      .flatten()
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().map_or(false, |ext| ext == "txt") || sets::is_set_file(path))
+      .collect();
+    overrides.sort();
+    files.extend(overrides);
+  }
+  files
+}
+
+/// Parses `!U+XXXX` or `!U+XXXX..U+YYYY`, which hide characters
+fn parse_hidden(line: &str) -> Option<(u32, u32)> {
+  let range = line.trim_end().strip_prefix('!')?;
+  let cp = |s: &str| {
+    let s = s.strip_prefix("U+").or_else(|| s.strip_prefix("u+"))?;
+    ucd::parse_cp(s)
+  };
+  match range.split_once("..") {
+    Some((first, last)) => Some((cp(first)?, cp(last)?)),
+    None => cp(range).map(|c| (c, c)),
+  }
+}
+
+/// Properties that add to earlier values instead of replacing them
+const LIST_PROPS: [&str; 2] = ["kw", "tags"];
+
+/// Applies a later line for the same characters: a description replaces the
+/// old one, properties replace those with the same key, and keywords and tags
+/// are added
+fn merge(entry: &mut Entry, layer: Entry) {
+  if !layer.name.is_empty() {
+    entry.name = layer.name;
+  }
+  for keyword in layer.keywords {
+    if !entry.keywords.contains(&keyword) {
+      entry.keywords.push(keyword);
+    }
+  }
+  for (key, value) in layer.props {
+    match entry.props.iter_mut().find(|(k, _)| *k == key) {
+      Some(prop) if !LIST_PROPS.contains(&key.as_str()) => prop.1 = value,
+      _ => entry.props.push((key, value)),
+    }
+  }
+}
+
+/// A line of a layer file
+enum Line {
+  Entry(Entry),
+  /// Code points from the first to the last that are hidden
+  Hidden(u32, u32),
+}
+
+fn read_layer(path: &Path) -> io::Result<Vec<Line>> {
+  if sets::is_set_file(path) {
+    return Ok(sets::read_set(path)?.into_iter().map(Line::Entry).collect());
+  }
+  let mut layer = vec![];
+  // Entries under a `[section]` heading are tagged with the section name,
+  // up to the next heading or the end of the file
+  let mut section: Option<String> = None;
+  for line in read_lines(path)?.flatten() {
+    if let Some(name) = search::parse_section(&line) {
+      section = Some(name.to_string());
+    } else if let Some((first, last)) = parse_hidden(&line) {
+      layer.push(Line::Hidden(first, last));
+    } else if let Some(mut entry) = search::parse_line(&line) {
+      if let Some(section) = &section {
+        entry.props.push(("tags".to_string(), section.clone()));
+      }
+      layer.push(Line::Entry(entry));
+    }
+  }
+  Ok(layer)
+}
+
+/// The entries of the files merged, in the order they first appear, and a
+/// message for each file after the first that could not be read. Only the
+/// first file has to exist.
+fn merge_files(files: &[PathBuf]) -> io::Result<(Vec<Entry>, Vec<String>)> {
+  let mut errors = vec![];
+  let mut entries: Vec<Option<Entry>> = vec![];
+  let mut positions: HashMap<String, usize> = HashMap::new();
+  for (i, path) in files.iter().enumerate() {
+    let layer = match read_layer(path) {
+      Ok(layer) => layer,
+      Err(e) if i == 0 => return Err(e),
+      Err(e) => {
+        errors.push(format!("{} could not be read: {}", path.display(), e));
+        continue;
+      }
+    };
+    for line in layer {
+      let entry = match line {
+        Line::Entry(entry) => entry,
+        Line::Hidden(first, last) => {
+          for slot in entries.iter_mut() {
+            let cp = slot.as_ref().and_then(Entry::code_point).map(|c| c as u32);
+            if cp.map_or(false, |cp| first <= cp && cp <= last) {
+              *slot = None;
+            }
+          }
+          continue;
+        }
+      };
+      match positions
+        .get(&entry.chars)
+        .and_then(|&p| entries[p].as_mut())
+      {
+        Some(existing) => merge(existing, entry),
+        None => {
+          positions.insert(entry.chars.clone(), entries.len());
+          entries.push(Some(entry));
+        }
+      }
+    }
+  }
+  Ok((entries.into_iter().flatten().collect(), errors))
+}
+
+/// The entries of all the layer files merged, and a message for each override
+/// file that could not be read
+pub fn load_entries() -> io::Result<(Vec<Entry>, Vec<String>)> {
+  merge_files(&layer_files())
+}
+
+/// The entries of a single characters file or set
+pub fn load_file(path: &Path) -> io::Result<Vec<Entry>> {
+  Ok(merge_files(&[path.to_path_buf()])?.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tempdir::TempDir;
+
+  /// Writes the files into a new temporary folder and returns their paths. The
+  /// folder is deleted when the returned `TempDir` is dropped.
+  fn write_files(test: &str, files: &[(&str, &str)]) -> (TempDir, Vec<PathBuf>) {
+    let dir = TempDir::new(test);
+    let paths = files
+      .iter()
+      .map(|(name, contents)| dir.write(name, contents))
+      .collect();
+    (dir, paths)
+  }
+
+  fn summary(entries: &[Entry]) -> Vec<String> {
+    entries.iter().map(search::format_line).collect()
+  }
+
+  #[test]
+  fn later_files_override_earlier_ones() {
+    let (_dir, files) = write_files(
+      "override",
+      &[
+        (
+          "characters.txt",
+          "Δ GREEK CAPITAL LETTER DELTA ; gc=Lu ; kw=delta\n→ RIGHTWARDS ARROW\n",
+        ),
+        (
+          "b.txt",
+          "Δ ; gc=Sm ; kw=triangle\n→ | to\n→ arrow | to, then\n",
+        ),
+      ],
+    );
+    assert_eq!(
+      summary(&merge_files(&files).unwrap().0),
+      vec![
+        "Δ GREEK CAPITAL LETTER DELTA ; gc=Sm ; kw=delta ; kw=triangle",
+        "→ arrow | to, then",
+      ]
+    );
+  }
+
+  #[test]
+  fn hidden_characters_can_be_added_back() {
+    let (_dir, files) = write_files(
+      "hidden",
+      &[
+        (
+          "characters.txt",
+          "← LEFTWARDS ARROW\n→ RIGHTWARDS ARROW\nΔ DELTA\n",
+        ),
+        ("a.txt", "!U+2190..U+21FF\r\n"),
+        ("b.txt", "→ arrow\n"),
+      ],
+    );
+    assert_eq!(
+      summary(&merge_files(&files).unwrap().0),
+      vec!["Δ DELTA", "→ arrow"]
+    );
+  }
+
+  #[test]
+  fn sections_tag_the_entries_below_them() {
+    let (_dir, files) = write_files(
+      "sections",
+      &[(
+        "characters.txt",
+        "# symbols\r\n\r\n[Logic]\r\n⇒ IMPLIES\r\n\\# NUMBER SIGN ; tags=ascii\r\n[Marks]\r\n✓ CHECK\r\n",
+      )],
+    );
+    assert_eq!(
+      summary(&merge_files(&files).unwrap().0),
+      vec![
+        "⇒ IMPLIES ; tags=Logic",
+        "\\# NUMBER SIGN ; tags=ascii ; tags=Logic",
+        "✓ CHECK ; tags=Marks",
+      ]
+    );
+  }
+
+  #[test]
+  fn sets_merge_like_lines() {
+    let (_dir, files) = write_files(
+      "sets",
+      &[
+        ("characters.txt", "✓ CHECK MARK\n"),
+        (
+          "team.csv",
+          "chars,name,keywords,tags\n✓,,tick,Marks\n★,BLACK STAR,,\n",
+        ),
+      ],
+    );
+    assert_eq!(
+      summary(&merge_files(&files).unwrap().0),
+      vec!["✓ CHECK MARK | tick ; tags=Marks", "★ BLACK STAR"]
+    );
+  }
+
+  #[test]
+  fn reports_sets_that_cannot_be_read() {
+    let (_dir, files) = write_files(
+      "malformed",
+      &[
+        ("characters.txt", "Δ DELTA\n"),
+        ("team.json", "[{\"chars\": "),
+        ("team.txt", "→ RIGHTWARDS ARROW\n"),
+      ],
+    );
+    let (entries, errors) = merge_files(&files).unwrap();
+    assert_eq!(summary(&entries), vec!["Δ DELTA", "→ RIGHTWARDS ARROW"]);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("team.json"), "{}", errors[0]);
+  }
+
+  #[test]
+  fn only_the_first_file_is_required() {
+    let (_dir, files) = write_files("missing", &[("characters.txt", "Δ DELTA\n")]);
+    let missing = files[0].with_file_name("missing.txt");
+    assert_eq!(
+      summary(&merge_files(&[files[0].clone(), missing.clone()]).unwrap().0),
+      vec!["Δ DELTA"]
+    );
+    assert!(merge_files(&[missing]).is_err());
+  }
+}
@@ -5,13 +5,59 @@ mod winplat;
 
 // mod icy;
 mod caret;
+mod cldr;
+mod compose;
+mod digraphs;
+mod entities;
+mod favorites;
+mod history;
+mod inspect;
 mod kblock;
+mod layers;
+mod query;
+mod relations;
 mod search;
+mod sets;
+mod storage;
+#[cfg(test)]
+mod tempdir;
+mod ucd;
 mod ui;
 
 #[macro_use]
 extern crate tantivy;
 
+fn gen_data(args: &[String]) {
+  winplat::attach_console();
+  let result = ucd::GenOptions::from_args(args)
+    .and_then(|opts| ucd::gen_data(&opts).map_err(|e| e.to_string()));
+  match result {
+    Ok(count) => println!("Wrote {} characters", count),
+    Err(e) => {
+      eprintln!("gen-data: {}", e);
+      std::process::exit(1);
+    }
+  }
+}
+
+fn export(args: &[String]) {
+  winplat::attach_console();
+  let result = sets::ExportOptions::from_args(args)
+    .and_then(|opts| sets::export(&opts).map_err(|e| e.to_string()));
+  match result {
+    Ok(count) => println!("Exported {} characters", count),
+    Err(e) => {
+      eprintln!("export: {}", e);
+      std::process::exit(1);
+    }
+  }
+}
+
 fn main() {
-  ui::main().unwrap();
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match args.first().map(String::as_str) {
+    Some("gen-data") => gen_data(&args[1..]),
+    Some("export") => export(&args[1..]),
+    _ => ui::main().unwrap(),
+  }
 }
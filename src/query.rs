@@ -0,0 +1,508 @@
+use crate::search::SearchEngine;
+use crate::ucd;
+use std::iter::Peekable;
+use std::str::Chars;
+use tantivy::query::{
+  AllQuery, BooleanQuery, BoostQuery, EmptyQuery, Occur, Query, RegexQuery, TermQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::tokenizer::{TextAnalyzer, TokenStream};
+use tantivy::{self, Score, Term};
+
+/// How much more a whole word counts than a word that only starts with the query
+const WHOLE_WORD_BOOST: Score = 2.0;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+  Open,
+  Close,
+  And,
+  Or,
+  Not,
+  Exclude,
+  Require,
+  Phrase(String),
+  Word(String),
+  Field(String, String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Ast {
+  /// Matches words that start with this text
+  Word(String),
+  Phrase(String),
+  /// Searches only one field, as in `block:arrows` or `block:"box drawing"`
+  Field(String, String),
+  Not(Box<Ast>),
+  And(Vec<Ast>),
+  Or(Vec<Ast>),
+}
+
+/// Reads up to the closing quote
+fn read_phrase(chars: &mut Peekable<Chars>) -> Result<String, String> {
+  let mut phrase = String::new();
+  loop {
+    match chars.next() {
+      Some('"') => return Ok(phrase),
+      Some(c) => phrase.push(c),
+      None => return Err("missing closing quote".to_string()),
+    }
+  }
+}
+
+/// Splits the text into tokens. Only the names in `fields` are read as field
+/// prefixes, so that a digraph such as `a:` is a word.
+fn lex(s: &str, fields: &[&str]) -> Result<Vec<Token>, String> {
+  let mut tokens = vec![];
+  let mut chars = s.chars().peekable();
+  while let Some(&c) = chars.peek() {
+    match c {
+      _ if c.is_whitespace() => {
+        chars.next();
+      }
+      '(' => {
+        chars.next();
+        tokens.push(Token::Open);
+      }
+      ')' => {
+        chars.next();
+        tokens.push(Token::Close);
+      }
+      '"' => {
+        chars.next();
+        tokens.push(Token::Phrase(read_phrase(&mut chars)?));
+      }
+      '-' | '+' => {
+        chars.next();
+        match chars.peek() {
+          Some(&next) if !next.is_whitespace() => {
+            tokens.push(if c == '-' {
+              Token::Exclude
+            } else {
+              Token::Require
+            });
+          }
+          _ => tokens.push(Token::Word(c.to_string())),
+        }
+      }
+      _ => {
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+            break;
+          }
+          word.push(c);
+          chars.next();
+        }
+        let field = match word.split_once(':') {
+          Some((field, value)) if fields.iter().any(|f| f.eq_ignore_ascii_case(field)) => {
+            Some((field.to_string(), value.to_string()))
+          }
+          _ => None,
+        };
+        tokens.push(match (word.as_str(), field) {
+          (_, Some((field, value))) if value.is_empty() && chars.peek() == Some(&'"') => {
+            chars.next();
+            Token::Field(field, read_phrase(&mut chars)?)
+          }
+          (_, Some((field, value))) => Token::Field(field, value),
+          ("AND", _) => Token::And,
+          ("OR", _) => Token::Or,
+          ("NOT", _) => Token::Not,
+          _ => Token::Word(word),
+        });
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+
+  fn next(&mut self) -> Option<&Token> {
+    self.pos += 1;
+    self.tokens.get(self.pos - 1)
+  }
+
+  fn or(&mut self) -> Result<Ast, String> {
+    let mut terms = vec![self.and()?];
+    while self.peek() == Some(&Token::Or) {
+      self.next();
+      terms.push(self.and()?);
+    }
+    Ok(if terms.len() == 1 {
+      terms.pop().unwrap()
+    } else {
+      Ast::Or(terms)
+    })
+  }
+
+  /// Terms next to each other must all match
+  fn and(&mut self) -> Result<Ast, String> {
+    let mut terms = vec![self.unary()?];
+    loop {
+      match self.peek() {
+        None | Some(Token::Close) | Some(Token::Or) => break,
+        Some(Token::And) => {
+          self.next();
+        }
+        _ => (),
+      }
+      terms.push(self.unary()?);
+    }
+    Ok(if terms.len() == 1 {
+      terms.pop().unwrap()
+    } else {
+      Ast::And(terms)
+    })
+  }
+
+  fn unary(&mut self) -> Result<Ast, String> {
+    match self.next() {
+      Some(Token::Not) | Some(Token::Exclude) => Ok(Ast::Not(Box::new(self.unary()?))),
+      Some(Token::Require) => self.unary(),
+      Some(Token::Open) => {
+        let ast = self.or()?;
+        match self.next() {
+          Some(Token::Close) => Ok(ast),
+          _ => Err("missing closing parenthesis".to_string()),
+        }
+      }
+      Some(Token::Phrase(p)) => Ok(Ast::Phrase(p.clone())),
+      Some(Token::Word(w)) => Ok(Ast::Word(w.clone())),
+      Some(Token::Field(field, value)) => Ok(Ast::Field(field.clone(), value.clone())),
+      Some(Token::Close) => Err("unexpected closing parenthesis".to_string()),
+      Some(Token::And) => Err("expected a search term before AND".to_string()),
+      Some(Token::Or) => Err("expected a search term before OR".to_string()),
+      None => Err("expected a search term at the end".to_string()),
+    }
+  }
+}
+
+pub fn parse(s: &str, fields: &[&str]) -> Result<Option<Ast>, String> {
+  let tokens = lex(s, fields)?;
+  if tokens.is_empty() {
+    return Ok(None);
+  }
+  let mut parser = Parser { tokens, pos: 0 };
+  let ast = parser.or()?;
+  match parser.peek() {
+    None => Ok(Some(ast)),
+    Some(_) => Err("unexpected closing parenthesis".to_string()),
+  }
+}
+
+fn analyze(analyzer: &TextAnalyzer, text: &str) -> Vec<String> {
+  let mut stream = analyzer.token_stream(text);
+  let mut tokens = vec![];
+  while stream.advance() {
+    tokens.push(stream.token().text.clone());
+  }
+  tokens
+}
+
+/// Characters with a meaning in the regex syntax of the term dictionary.
+/// Escaping any other character, such as `:` or `>`, is an error there.
+const REGEX_META: &str = r"\.+*?()|[]{}^$#&-~";
+
+fn escape_regex(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    if REGEX_META.contains(c) {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+/// Matches a word in the field exactly or as the start of a longer word
+fn prefix_query(field: Field, token: &str) -> tantivy::Result<Box<dyn Query>> {
+  let exact = TermQuery::new(
+    Term::from_field_text(field, token),
+    IndexRecordOption::WithFreqs,
+  );
+  let prefix = RegexQuery::from_pattern(&format!("{}.*", escape_regex(token)), field)?;
+  Ok(Box::new(BooleanQuery::new(vec![
+    (
+      Occur::Should,
+      Box::new(BoostQuery::new(Box::new(exact), WHOLE_WORD_BOOST)),
+    ),
+    (Occur::Should, Box::new(prefix)),
+  ])))
+}
+
+/// Matches documents where the field has words starting with each word of
+/// the text. `None` when the text has nothing searchable in it, such as punctuation.
+fn field_query(
+  eng: &SearchEngine,
+  field: Field,
+  text: &str,
+) -> tantivy::Result<Option<Box<dyn Query>>> {
+  let tokens = analyze(&eng.index.tokenizer_for_field(field)?, text);
+  if tokens.is_empty() {
+    return Ok(None);
+  }
+  let mut token_queries = vec![];
+  for token in tokens {
+    token_queries.push((Occur::Must, prefix_query(field, &token)?));
+  }
+  Ok(Some(Box::new(BooleanQuery::new(token_queries))))
+}
+
+/// Matches a general category code such as `Lu` or `L` exactly, and
+/// otherwise the words of the long names, as in `cat:upper`
+fn category_query(eng: &SearchEngine, value: &str) -> tantivy::Result<Option<Box<dyn Query>>> {
+  match ucd::category_code(value.trim()) {
+    Some(code) => Ok(Some(Box::new(TermQuery::new(
+      Term::from_field_text(eng.category_field, code),
+      IndexRecordOption::Basic,
+    )))),
+    None => field_query(eng, eng.category_name_field, value),
+  }
+}
+
+fn word_query(eng: &SearchEngine, word: &str) -> tantivy::Result<Option<Box<dyn Query>>> {
+  let mut field_queries: Vec<(Occur, Box<dyn Query>)> = vec![];
+  for &(field, boost) in &eng.default_fields {
+    if let Some(q) = field_query(eng, field, word)? {
+      field_queries.push((Occur::Should, Box::new(BoostQuery::new(q, boost))));
+    }
+  }
+  if field_queries.is_empty() {
+    Ok(None)
+  } else {
+    Ok(Some(Box::new(BooleanQuery::new(field_queries))))
+  }
+}
+
+fn compile(eng: &SearchEngine, ast: &Ast) -> tantivy::Result<Option<Box<dyn Query>>> {
+  match ast {
+    Ast::Word(w) => word_query(eng, w),
+    Ast::Phrase(p) => Ok(Some(eng.query_parser.parse_query(&format!("\"{}\"", p))?)),
+    Ast::Field(name, value) => {
+      let field = eng
+        .prefixed_fields
+        .iter()
+        .find(|(prefix, _)| prefix.eq_ignore_ascii_case(name))
+        .map(|(_, field)| *field)
+        .ok_or_else(|| {
+          tantivy::TantivyError::InvalidArgument(format!("unknown field {}:", name))
+        })?;
+      if field == eng.category_field {
+        category_query(eng, value)
+      } else {
+        field_query(eng, field, value)
+      }
+    }
+    Ast::Not(_) => compile_and(eng, &[ast]),
+    Ast::And(terms) => compile_and(eng, &terms.iter().collect::<Vec<_>>()),
+    Ast::Or(terms) => {
+      let mut clauses = vec![];
+      for t in terms {
+        if let Some(q) = compile(eng, t)? {
+          clauses.push((Occur::Should, q));
+        }
+      }
+      Ok(if clauses.is_empty() {
+        None
+      } else {
+        Some(Box::new(BooleanQuery::new(clauses)))
+      })
+    }
+  }
+}
+
+fn compile_and(eng: &SearchEngine, terms: &[&Ast]) -> tantivy::Result<Option<Box<dyn Query>>> {
+  let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+  for t in terms {
+    let (occur, q) = match t {
+      Ast::Not(inner) => (Occur::MustNot, compile(eng, inner)?),
+      _ => (Occur::Must, compile(eng, t)?),
+    };
+    if let Some(q) = q {
+      clauses.push((occur, q));
+    }
+  }
+  if clauses.is_empty() {
+    return Ok(None);
+  }
+  // Exclusions only remove results, so something has to match first
+  if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+    clauses.push((Occur::Must, Box::new(AllQuery)));
+  }
+  Ok(Some(Box::new(BooleanQuery::new(clauses))))
+}
+
+/// Builds the query for the search box text
+pub fn build_query(eng: &SearchEngine, s: &str) -> tantivy::Result<Box<dyn Query>> {
+  let fields: Vec<&str> = eng
+    .prefixed_fields
+    .iter()
+    .map(|(prefix, _)| prefix.as_str())
+    .collect();
+  let ast = parse(s, &fields).map_err(tantivy::TantivyError::InvalidArgument)?;
+  let query = match ast {
+    Some(ast) => compile(eng, &ast)?,
+    None => None,
+  };
+  Ok(query.unwrap_or_else(|| Box::new(EmptyQuery)))
+}
+
+/// Reads every word as plain text, for queries that cannot be parsed, such
+/// as ones with an unbalanced quote that is still being typed
+pub fn build_lenient_query(eng: &SearchEngine, s: &str) -> tantivy::Result<Box<dyn Query>> {
+  let words: Vec<Ast> = s
+    .split(|c: char| c.is_whitespace() || c == '"' || c == '(' || c == ')')
+    .map(|w| w.trim_start_matches(|c| c == '-' || c == '+'))
+    .filter(|w| !w.is_empty())
+    .map(|w| Ast::Word(w.to_string()))
+    .collect();
+  let query = compile_and(eng, &words.iter().collect::<Vec<_>>())?;
+  Ok(query.unwrap_or_else(|| Box::new(EmptyQuery)))
+}
+
+/// The message to show for a query that could not be built
+pub fn error_message(e: &tantivy::TantivyError) -> String {
+  match e {
+    tantivy::TantivyError::InvalidArgument(msg) => msg.clone(),
+    e => e.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FIELDS: &[&str] = &["block", "cat"];
+
+  fn word(w: &str) -> Ast {
+    Ast::Word(w.to_string())
+  }
+
+  #[test]
+  fn lexes_words_phrases_and_fields() {
+    assert_eq!(
+      lex(
+        r#"gre -cap "latin small" block:"box drawing" cat:Lu"#,
+        FIELDS
+      )
+      .unwrap(),
+      vec![
+        Token::Word("gre".to_string()),
+        Token::Exclude,
+        Token::Word("cap".to_string()),
+        Token::Phrase("latin small".to_string()),
+        Token::Field("block".to_string(), "box drawing".to_string()),
+        Token::Field("cat".to_string(), "Lu".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn dash_on_its_own_is_a_word() {
+    assert_eq!(
+      lex("a - b +", FIELDS).unwrap(),
+      vec![
+        Token::Word("a".to_string()),
+        Token::Word("-".to_string()),
+        Token::Word("b".to_string()),
+        Token::Word("+".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn only_known_fields_are_prefixes() {
+    assert_eq!(parse("a:", FIELDS).unwrap(), Some(word("a:")));
+    assert_eq!(parse("http://x", FIELDS).unwrap(), Some(word("http://x")));
+    assert_eq!(
+      parse("CAT:lu", FIELDS).unwrap(),
+      Some(Ast::Field("CAT".to_string(), "lu".to_string()))
+    );
+  }
+
+  #[test]
+  fn words_next_to_each_other_must_all_match() {
+    assert_eq!(
+      parse("gre cap del", FIELDS).unwrap(),
+      Some(Ast::And(vec![word("gre"), word("cap"), word("del")]))
+    );
+  }
+
+  #[test]
+  fn or_binds_looser_than_and() {
+    assert_eq!(
+      parse(r#"(greek AND letter) OR "latin capital""#, FIELDS).unwrap(),
+      Some(Ast::Or(vec![
+        Ast::And(vec![word("greek"), word("letter")]),
+        Ast::Phrase("latin capital".to_string()),
+      ]))
+    );
+  }
+
+  #[test]
+  fn exclusions() {
+    assert_eq!(
+      parse("arrow -double NOT left", FIELDS).unwrap(),
+      Some(Ast::And(vec![
+        word("arrow"),
+        Ast::Not(Box::new(word("double"))),
+        Ast::Not(Box::new(word("left"))),
+      ]))
+    );
+    assert_eq!(parse("+arrow", FIELDS).unwrap(), Some(word("arrow")));
+  }
+
+  #[test]
+  fn empty_query() {
+    assert_eq!(parse("   ", FIELDS).unwrap(), None);
+  }
+
+  #[test]
+  fn unbalanced_queries_are_errors() {
+    assert_eq!(
+      parse(r#""latin cap"#, FIELDS).unwrap_err(),
+      "missing closing quote"
+    );
+    assert_eq!(
+      parse("(greek", FIELDS).unwrap_err(),
+      "missing closing parenthesis"
+    );
+    assert_eq!(
+      parse("greek)", FIELDS).unwrap_err(),
+      "unexpected closing parenthesis"
+    );
+    assert_eq!(
+      parse("greek OR", FIELDS).unwrap_err(),
+      "expected a search term at the end"
+    );
+    assert_eq!(
+      parse("OR greek", FIELDS).unwrap_err(),
+      "expected a search term before OR"
+    );
+  }
+
+  #[test]
+  fn escapes_regex_punctuation() {
+    assert_eq!(escape_regex("a.b*"), r"a\.b\*");
+    assert_eq!(escape_regex("->"), r"\->");
+    assert_eq!(escape_regex("a:'e"), "a:'e");
+  }
+
+  #[test]
+  fn prefix_queries_accept_any_punctuation() {
+    let field = Field::from_field_id(0);
+    for token in ["->", "a:", "'e", "<=", "a.b*", "\\"] {
+      assert!(prefix_query(field, token).is_ok(), "{}", token);
+    }
+  }
+}
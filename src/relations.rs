@@ -0,0 +1,273 @@
+use crate::ucd;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Groups of characters that are related in some way, looked up by any
+/// member of the group
+#[derive(Default)]
+pub struct RelationIndex {
+  groups: Vec<Vec<String>>,
+  group_of: HashMap<String, usize>,
+}
+
+impl RelationIndex {
+  /// Puts the characters in the same group, joining any groups they are already in
+  fn link(&mut self, a: &str, b: &str) {
+    let group = match (self.group_of.get(a), self.group_of.get(b)) {
+      (Some(&ga), Some(&gb)) if ga == gb => return,
+      (Some(&ga), Some(&gb)) => {
+        let moved = std::mem::take(&mut self.groups[gb]);
+        for chars in &moved {
+          self.group_of.insert(chars.clone(), ga);
+        }
+        self.groups[ga].extend(moved);
+        return;
+      }
+      (Some(&g), None) | (None, Some(&g)) => g,
+      (None, None) => {
+        self.groups.push(vec![]);
+        self.groups.len() - 1
+      }
+    };
+    for chars in [a, b] {
+      if !self.group_of.contains_key(chars) {
+        self.group_of.insert(chars.to_string(), group);
+        self.groups[group].push(chars.to_string());
+      }
+    }
+  }
+
+  fn link_cps(&mut self, a: u32, b: u32) {
+    if let (Some(a), Some(b)) = (char::from_u32(a), char::from_u32(b)) {
+      self.link(&a.to_string(), &b.to_string());
+    }
+  }
+
+  /// The other characters in the group of these characters
+  pub fn related<'a>(&'a self, chars: &'a str) -> impl Iterator<Item = &'a str> {
+    self
+      .group_of
+      .get(chars)
+      .into_iter()
+      .flat_map(move |&g| self.groups[g].iter())
+      .map(String::as_str)
+      .filter(move |c| *c != chars)
+  }
+}
+
+/// Reads `confusables.txt` from the Unicode security data, where each line
+/// maps a character to the prototype that it looks like
+fn read_confusables<P: AsRef<Path>>(path: P) -> RelationIndex {
+  let mut index = RelationIndex::default();
+  for fields in ucd::data_lines(path).into_iter().flatten() {
+    if fields.len() < 2 {
+      continue;
+    }
+    // The file starts with a byte order mark
+    let source = ucd::parse_chars(fields[0].trim_start_matches('\u{FEFF}'));
+    if let (Some(source), Some(prototype)) = (source, ucd::parse_chars(&fields[1])) {
+      index.link(&source, &prototype);
+    }
+  }
+  index
+}
+
+/// Links each precomposed character in UnicodeData.txt to the base character
+/// that its canonical decomposition starts with, so that `e`, `é` and `ễ` are
+/// all variants of each other
+fn read_variants(records: &[ucd::Record]) -> RelationIndex {
+  let first_parts: HashMap<u32, u32> = records
+    .iter()
+    .filter(|r| !r.decomposition.starts_with('<'))
+    .filter_map(|r| {
+      let first = r.decomposition.split_whitespace().next()?;
+      Some((r.cp, ucd::parse_cp(first)?))
+    })
+    .collect();
+  let mut index = RelationIndex::default();
+  for r in records {
+    let mut base = match first_parts.get(&r.cp) {
+      Some(&first) => first,
+      None => continue,
+    };
+    while let Some(&first) = first_parts.get(&base) {
+      base = first;
+    }
+    index.link_cps(base, r.cp);
+  }
+  index
+}
+
+/// Links characters to their other cases and to the mathematical styles of
+/// them, such as `𝐀` and `𝒜`, which have a `<font>` decomposition
+fn read_related(records: &[ucd::Record]) -> RelationIndex {
+  let mut index = RelationIndex::default();
+  for r in records {
+    for mapping in &r.case_mappings {
+      index.link_cps(r.cp, *mapping);
+    }
+    if let Some(styled) = r.decomposition.strip_prefix("<font> ") {
+      if let Some(base) = ucd::parse_cp(styled) {
+        index.link_cps(base, r.cp);
+      }
+    }
+  }
+  index
+}
+
+/// Code points that have a record, with the ranges given by `First` and
+/// `Last` records such as CJK ideographs
+fn assigned_ranges(records: &[ucd::Record]) -> Vec<(u32, u32)> {
+  let mut ranges = vec![];
+  let mut range_first = None;
+  for r in records {
+    if r.name.ends_with(", First>") {
+      range_first = Some(r.cp);
+    } else {
+      ranges.push((range_first.take().unwrap_or(r.cp), r.cp));
+    }
+  }
+  ranges
+}
+
+/// A kind of relation that can be listed with a prefix such as `confusable:а`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+  Confusable,
+  /// Characters with the same base letter, such as `é` and `è`
+  Variant,
+  /// Other cases, mathematical styles and neighbouring code points in the same block
+  Related,
+}
+
+impl Relation {
+  const PREFIXES: [(&'static str, Relation); 5] = [
+    ("confusable:", Relation::Confusable),
+    ("confusables:", Relation::Confusable),
+    ("variant:", Relation::Variant),
+    ("variants:", Relation::Variant),
+    ("related:", Relation::Related),
+  ];
+
+  /// The prefix used to list this relation
+  pub fn prefix(self) -> &'static str {
+    match self {
+      Relation::Confusable => "confusable:",
+      Relation::Variant => "variants:",
+      Relation::Related => "related:",
+    }
+  }
+
+  /// Splits a search such as `confusable:а` into the relation and the text after it.
+  /// Only ASCII spaces are trimmed, so that `confusable:` followed by a no-break
+  /// space looks that up, and a space on its own is kept.
+  pub fn parse(s: &str) -> Option<(Relation, &str)> {
+    let s = s.trim_start_matches(' ');
+    Self::PREFIXES.iter().find_map(|&(prefix, relation)| {
+      let head = s.get(..prefix.len())?;
+      if !head.eq_ignore_ascii_case(prefix) {
+        return None;
+      }
+      let rest = &s[prefix.len()..];
+      match rest.trim_matches(' ') {
+        "" if !rest.is_empty() => Some((relation, " ")),
+        target => Some((relation, target)),
+      }
+    })
+  }
+}
+
+/// Code points on each side of a character that are listed as its neighbours
+const NEIGHBOURS: u32 = 8;
+
+/// Relation indexes loaded from the UCD folder, beside the search index
+#[derive(Default)]
+pub struct Relations {
+  confusables: RelationIndex,
+  variants: RelationIndex,
+  related: RelationIndex,
+  assigned: Vec<(u32, u32)>,
+  blocks: Vec<ucd::RangeValue>,
+}
+
+impl Relations {
+  pub fn load() -> Relations {
+    let dir = Path::new(ucd::UCD_DIR);
+    let records = ucd::read_unicode_data(dir.join(ucd::UNICODE_DATA_FILE)).unwrap_or_default();
+    Relations {
+      confusables: read_confusables(dir.join(ucd::CONFUSABLES_FILE)),
+      variants: read_variants(&records),
+      related: read_related(&records),
+      assigned: assigned_ranges(&records),
+      blocks: ucd::read_ranges(dir.join(ucd::BLOCKS_FILE)).unwrap_or_default(),
+    }
+  }
+
+  fn is_assigned(&self, cp: u32) -> bool {
+    let idx = self.assigned.partition_point(|&(first, _)| first <= cp);
+    idx > 0 && cp <= self.assigned[idx - 1].1
+  }
+
+  /// Assigned code points around a character, in the same block
+  fn neighbours(&self, c: char) -> Vec<String> {
+    let cp = c as u32;
+    let (first, last) = match self.blocks.iter().find(|b| b.first <= cp && cp <= b.last) {
+      Some(block) => (block.first, block.last),
+      None => return vec![],
+    };
+    (cp.saturating_sub(NEIGHBOURS).max(first)..=(cp + NEIGHBOURS).min(last))
+      .filter(|&n| n != cp && self.is_assigned(n))
+      .filter_map(char::from_u32)
+      .map(String::from)
+      .collect()
+  }
+
+  /// The characters related to these ones, in the order they are listed
+  pub fn related(&self, relation: Relation, chars: &str) -> Vec<String> {
+    let index = match relation {
+      Relation::Confusable => &self.confusables,
+      Relation::Variant => &self.variants,
+      Relation::Related => &self.related,
+    };
+    let mut related: Vec<String> = index.related(chars).map(String::from).collect();
+    if let Relation::Related = relation {
+      let mut single = chars.chars();
+      if let (Some(c), None) = (single.next(), single.next()) {
+        for n in self.neighbours(c) {
+          if !related.contains(&n) {
+            related.push(n);
+          }
+        }
+      }
+    }
+    related
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_relation_prefixes() {
+    assert_eq!(
+      Relation::parse(" Confusable: а "),
+      Some((Relation::Confusable, "а"))
+    );
+    assert_eq!(Relation::parse("variants:"), Some((Relation::Variant, "")));
+    assert_eq!(Relation::parse("arrow"), None);
+  }
+
+  #[test]
+  fn keeps_spaces_that_are_the_target() {
+    assert_eq!(
+      Relation::parse("confusable:\u{a0}"),
+      Some((Relation::Confusable, "\u{a0}"))
+    );
+    assert_eq!(
+      Relation::parse("related:\u{2003}"),
+      Some((Relation::Related, "\u{2003}"))
+    );
+    assert_eq!(Relation::parse("related: "), Some((Relation::Related, " ")));
+  }
+}
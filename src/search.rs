@@ -1,18 +1,164 @@
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
-use tantivy::query::QueryParser;
-use tantivy::{self, schema, Index, ReloadPolicy};
+use crate::cldr;
+use crate::compose;
+use crate::digraphs;
+use crate::entities;
+use crate::layers;
+use crate::storage;
+use crate::ucd;
+use rustc_hash::FxHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::IndexRecordOption;
+use tantivy::tokenizer::{
+  Language, LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer,
+};
+use tantivy::{self, schema, Document, Index, IndexWriter, ReloadPolicy, Searcher, Term};
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-  P: AsRef<Path>,
-{
-  let file = File::open(filename)?;
-  Ok(io::BufReader::new(file).lines())
+pub const CHAR_FILE: &str = "characters.txt";
+
+/// A line of the characters file: the character or sequence of characters,
+/// a space, its description, optional ` | keyword, keyword` words of your own,
+/// then optional ` ; key=value` properties
+pub struct Entry {
+  pub chars: String,
+  pub name: String,
+  pub keywords: Vec<String>,
+  pub props: Vec<(String, String)>,
+}
+
+impl Entry {
+  /// The code point of an entry that is a single character
+  pub fn code_point(&self) -> Option<char> {
+    let mut chars = self.chars.chars();
+    match (chars.next(), chars.next()) {
+      (Some(c), None) => Some(c),
+      _ => None,
+    }
+  }
+
+  pub fn prop(&self, key: &str) -> Option<&str> {
+    self
+      .props
+      .iter()
+      .find(|(k, _)| k == key)
+      .map(|(_, v)| v.as_str())
+  }
+
+  /// Values of a property that holds a comma-separated list, from every
+  /// time the property is given
+  pub fn prop_list<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+    self
+      .props
+      .iter()
+      .filter(move |(k, _)| k == key)
+      .flat_map(|(_, v)| v.split(','))
+      .map(str::trim)
+      .filter(|v| !v.is_empty())
+  }
+
+  /// Words added by the user, from the keyword section and `kw=` properties
+  pub fn user_keywords(&self) -> impl Iterator<Item = &str> {
+    self
+      .keywords
+      .iter()
+      .map(String::as_str)
+      .chain(self.prop_list("kw"))
+  }
 }
 
-const CHAR_FILE: &str = "characters.txt";
+/// Characters that start a line with a special meaning: a comment, a section
+/// heading or hidden characters
+const SPECIAL_STARTS: [char; 3] = ['#', '[', '!'];
+
+/// The characters as they are written at the start of a line, with a
+/// backslash before a character such as `#` that would start a comment
+pub fn escape_chars(chars: &str) -> String {
+  match chars.chars().next() {
+    Some(c) if SPECIAL_STARTS.contains(&c) => format!("\\{}", chars),
+    _ => chars.to_string(),
+  }
+}
+
+/// The name of a `[section]` heading
+pub fn parse_section(line: &str) -> Option<&str> {
+  let name = line.trim_end().strip_prefix('[')?.strip_suffix(']')?;
+  // `[ LEFT SQUARE BRACKET` and `[]` are characters
+  match name.chars().next() {
+    Some(c) if c != ' ' => Some(name.trim_end()),
+    _ => None,
+  }
+}
+
+/// Parses a line of the characters file. Blank lines and comments, which
+/// start with `#`, give `None`.
+pub fn parse_line(line: &str) -> Option<Entry> {
+  let line = line.strip_suffix('\r').unwrap_or(line);
+  if line.trim().is_empty() || line.starts_with('#') {
+    return None;
+  }
+  // `\#` stands for a line that lists `#` itself
+  let line = match line.strip_prefix('\\') {
+    Some(rest) if rest.starts_with(&SPECIAL_STARTS[..]) => rest,
+    _ => line,
+  };
+  let first_len = line.chars().next()?.len_utf8();
+  // A sequence such as an emoji with modifiers runs up to the first space.
+  // The first character may be a space itself.
+  let (chars, rest) = match line[first_len..].find(' ') {
+    Some(i) => (&line[..first_len + i], &line[first_len + i + 1..]),
+    None => (line, ""),
+  };
+  // An override line may leave out the description, as in `Δ ; kw=change`
+  // or `Δ | change`
+  let rest = match rest.strip_prefix("; ").or_else(|| rest.strip_prefix("| ")) {
+    Some(_) => format!(" {}", rest),
+    None => rest.to_string(),
+  };
+  let mut parts = rest.split(" ; ");
+  let description = parts.next().unwrap_or("");
+  let (name, keywords) = match description.split_once(" | ") {
+    Some((name, keywords)) => (name, keywords),
+    None => (description, ""),
+  };
+  let name = name.trim_end().to_string();
+  let keywords = keywords
+    .split(',')
+    .map(str::trim)
+    .filter(|k| !k.is_empty())
+    .map(str::to_string)
+    .collect();
+  let props = parts
+    .map(|p| match p.split_once('=') {
+      Some((k, v)) => (k.trim().to_string(), v.trim().to_string()),
+      None => (p.trim().to_string(), String::new()),
+    })
+    .collect();
+  Some(Entry {
+    chars: chars.to_string(),
+    name,
+    keywords,
+    props,
+  })
+}
+
+/// Writes an entry as a line of the characters file
+pub fn format_line(entry: &Entry) -> String {
+  let mut line = escape_chars(&entry.chars);
+  if !entry.name.is_empty() {
+    line = format!("{} {}", line, entry.name);
+  }
+  if !entry.keywords.is_empty() {
+    line = format!("{} | {}", line, entry.keywords.join(", "));
+  }
+  for (key, value) in &entry.props {
+    line = format!("{} ; {}={}", line, key, value);
+  }
+  line
+}
 
 #[derive(Clone)]
 pub struct SearchEngine {
@@ -22,6 +168,108 @@ pub struct SearchEngine {
   pub reader: tantivy::IndexReader,
   pub name_field: schema::Field,
   pub char_field: schema::Field,
+  pub cp_field: schema::Field,
+  pub alias_field: schema::Field,
+  pub keywords_field: schema::Field,
+  /// Words of your own from the characters file
+  pub user_keywords_field: schema::Field,
+  /// Sections and `tags=` from the characters file
+  pub tags_field: schema::Field,
+  pub block_field: schema::Field,
+  pub script_field: schema::Field,
+  /// General category codes, such as `Lu` and `L`
+  pub category_field: schema::Field,
+  /// Long names of the general category, such as `Uppercase_Letter Letter`
+  pub category_name_field: schema::Field,
+  pub age_field: schema::Field,
+  pub digraph_field: schema::Field,
+  pub entity_field: schema::Field,
+  pub compose_field: schema::Field,
+  /// Localized CLDR names, by locale
+  pub locale_fields: Vec<(String, schema::Field)>,
+  /// Fields searched by words without a field prefix, with their boosts
+  pub default_fields: Vec<(schema::Field, tantivy::Score)>,
+  /// Fields that can be searched with a prefix such as `block:arrows`
+  pub prefixed_fields: Vec<(String, schema::Field)>,
+  /// Fields whose values are typed out whole, such as a digraph. Characters
+  /// with a value equal to the query are shown first.
+  pub exact_fields: Vec<(&'static str, schema::Field)>,
+  /// Override files that could not be read when the index was built
+  pub load_errors: Vec<String>,
+}
+
+impl SearchEngine {
+  fn doc_of(&self, searcher: &Searcher, c: char) -> tantivy::Result<Option<Document>> {
+    let query = TermQuery::new(
+      Term::from_field_u64(self.cp_field, c as u64),
+      IndexRecordOption::Basic,
+    );
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(1))?;
+    match top_docs.first() {
+      Some((_score, doc_addr)) => Ok(Some(searcher.doc(*doc_addr)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// Description of a character if it is in the index
+  pub fn name_of(&self, searcher: &Searcher, c: char) -> tantivy::Result<Option<String>> {
+    Ok(self.doc_of(searcher, c)?.and_then(|doc| {
+      doc
+        .get_first(self.name_field)
+        .and_then(|v| v.text())
+        .map(String::from)
+    }))
+  }
+
+  /// Description and general category of a character if it is in the index.
+  /// The category is empty if the characters file does not give it.
+  pub fn properties_of(
+    &self,
+    searcher: &Searcher,
+    c: char,
+  ) -> tantivy::Result<Option<(String, String)>> {
+    Ok(self.doc_of(searcher, c)?.map(|doc| {
+      let text = |field| {
+        doc
+          .get_first(field)
+          .and_then(|v| v.text())
+          .unwrap_or("")
+          .to_string()
+      };
+      (text(self.name_field), text(self.category_field))
+    }))
+  }
+
+  /// Matches the documents of any of these characters or sequences
+  pub fn chars_query<'a>(&self, chars: impl Iterator<Item = &'a str>) -> BooleanQuery {
+    BooleanQuery::new(
+      chars
+        .map(|chars| {
+          let term = Term::from_field_text(self.char_field, chars);
+          let query: Box<dyn Query> = Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+          (Occur::Should, query)
+        })
+        .collect(),
+    )
+  }
+
+  /// Documents where the field has exactly this value
+  pub fn exact_matches(
+    &self,
+    searcher: &Searcher,
+    field: schema::Field,
+    value: &str,
+  ) -> tantivy::Result<Vec<Document>> {
+    let query = TermQuery::new(
+      Term::from_field_text(field, value),
+      IndexRecordOption::Basic,
+    );
+    let mut docs = vec![];
+    for (_score, doc_addr) in searcher.search(&query, &TopDocs::with_limit(10))? {
+      docs.push(searcher.doc(doc_addr)?);
+    }
+    Ok(docs)
+  }
 }
 
 impl druid::Data for SearchEngine {
@@ -33,42 +281,601 @@ impl druid::Data for SearchEngine {
   }
 }
 
-pub fn new_query_parser() -> tantivy::Result<SearchEngine> {
+fn locale_name_field(locale: &str) -> String {
+  format!("name_{}", locale)
+}
+
+fn locale_keywords_field(locale: &str) -> String {
+  format!("keywords_{}", locale)
+}
+
+fn locale_tokenizer(locale: &str) -> String {
+  format!("lang_{}", locale)
+}
+
+/// Splits text in a language into words, stemmed if tantivy has a stemmer
+/// for it. Languages written without spaces are split into single characters and pairs.
+fn locale_analyzer(locale: &str) -> TextAnalyzer {
+  let language = match locale.split('_').next().unwrap_or(locale) {
+    "ar" => Some(Language::Arabic),
+    "da" => Some(Language::Danish),
+    "de" => Some(Language::German),
+    "el" => Some(Language::Greek),
+    "es" => Some(Language::Spanish),
+    "fi" => Some(Language::Finnish),
+    "fr" => Some(Language::French),
+    "hu" => Some(Language::Hungarian),
+    "it" => Some(Language::Italian),
+    "nb" | "nn" | "no" => Some(Language::Norwegian),
+    "nl" => Some(Language::Dutch),
+    "pt" => Some(Language::Portuguese),
+    "ro" => Some(Language::Romanian),
+    "ru" => Some(Language::Russian),
+    "sv" => Some(Language::Swedish),
+    "ta" => Some(Language::Tamil),
+    "tr" => Some(Language::Turkish),
+    "ja" | "ko" | "th" | "yue" | "zh" => {
+      return TextAnalyzer::from(NgramTokenizer::new(1, 2, false)).filter(LowerCaser);
+    }
+    _ => None,
+  };
+  let analyzer = TextAnalyzer::from(SimpleTokenizer)
+    .filter(RemoveLongFilter::limit(40))
+    .filter(LowerCaser);
+  match language {
+    Some(language) => analyzer.filter(Stemmer::new(language)),
+    None => analyzer,
+  }
+}
+
+fn build_schema(locales: &[String]) -> schema::Schema {
   let mut schema_builder = schema::Schema::builder();
   schema_builder.add_text_field("name", schema::TEXT | schema::STORED);
-  schema_builder.add_text_field("char", schema::STORED);
-  let schema = schema_builder.build();
-  let index = Index::create_in_ram(schema.clone());
-  let mut index_writer = index.writer(50_000_000)?;
-  let name_field = schema.get_field("name").unwrap();
-  let char_field = schema.get_field("char").unwrap();
+  schema_builder.add_text_field("char", schema::STRING | schema::STORED);
+  schema_builder.add_u64_field("cp", schema::INDEXED | schema::FAST);
+  schema_builder.add_text_field("alias", schema::TEXT | schema::STORED);
+  schema_builder.add_text_field("keywords", schema::TEXT);
+  schema_builder.add_text_field("user_keywords", schema::TEXT | schema::STORED);
+  schema_builder.add_text_field("tags", schema::TEXT);
+  schema_builder.add_text_field("block", schema::TEXT);
+  schema_builder.add_text_field("script", schema::TEXT);
+  schema_builder.add_text_field("category", schema::STRING | schema::STORED);
+  schema_builder.add_text_field("category_name", schema::TEXT);
+  schema_builder.add_text_field("age", schema::STRING);
+  schema_builder.add_text_field("digraph", schema::STRING | schema::STORED);
+  schema_builder.add_text_field("entity", schema::STRING | schema::STORED);
+  schema_builder.add_text_field("compose", schema::STRING | schema::STORED);
+  for locale in locales {
+    let indexing = schema::TextFieldIndexing::default()
+      .set_tokenizer(&locale_tokenizer(locale))
+      .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let options = schema::TextOptions::default().set_indexing_options(indexing);
+    schema_builder.add_text_field(&locale_name_field(locale), options.clone().set_stored());
+    schema_builder.add_text_field(&locale_keywords_field(locale), options);
+  }
+  schema_builder.build()
+}
+
+/// Change this whenever the schema or the way documents are indexed changes,
+/// so that cached indexes are rebuilt
+const INDEX_VERSION: u32 = 14;
+
+/// Written once a cached index has all of its documents
+const INDEX_COMPLETE_FILE: &str = "complete";
+
+/// Files that the index is built from
+fn source_files() -> Vec<PathBuf> {
+  let mut files = layers::layer_files();
+  files.extend([
+    PathBuf::from(digraphs::DIGRAPHS_FILE),
+    PathBuf::from(entities::ENTITIES_FILE),
+  ]);
+  files.push(PathBuf::from(cldr::LOCALES_FILE));
+  files.extend(cldr::annotation_paths(cldr::DEFAULT_LOCALE));
+  for locale in cldr::locales() {
+    files.extend(cldr::annotation_paths(&locale));
+  }
+  files.extend(compose::compose_files());
+  // Parts of entity sequences that are not in the characters file are named from these
+  let ucd_dir = Path::new(ucd::UCD_DIR);
+  files.extend([
+    ucd_dir.join(ucd::UNICODE_DATA_FILE),
+    ucd_dir.join(ucd::DERIVED_NAME_FILE),
+  ]);
+  files
+}
+
+/// Identifies the current version of the source files
+fn sources_key() -> u64 {
+  let mut hasher = FxHasher::default();
+  INDEX_VERSION.hash(&mut hasher);
+  for path in source_files() {
+    path.hash(&mut hasher);
+    if let Ok(meta) = fs::metadata(&path) {
+      meta.len().hash(&mut hasher);
+      meta.modified().ok().hash(&mut hasher);
+    }
+  }
+  hasher.finish()
+}
+
+enum OpenedIndex {
+  /// Cached index that is up to date with the source files
+  Cached(Index),
+  /// Empty index, and the folder to mark as complete once it is filled
+  New(Index, Option<PathBuf>),
+}
+
+fn open_index(locales: &[String]) -> OpenedIndex {
+  let root = storage::data_dir().join("index");
+  let dir = root.join(format!("{:016x}", sources_key()));
+  if dir.join(INDEX_COMPLETE_FILE).exists() {
+    if let Ok(index) = Index::open_in_dir(&dir) {
+      return OpenedIndex::Cached(index);
+    }
+  }
+  // Clear out indexes of older source files and any incomplete one
+  if let Ok(entries) = fs::read_dir(&root) {
+    for entry in entries.flatten() {
+      let _ = fs::remove_dir_all(entry.path());
+    }
+  }
+  let index = fs::create_dir_all(&dir)
+    .map_err(tantivy::TantivyError::from)
+    .and_then(|_| Index::create_in_dir(&dir, build_schema(locales)));
+  match index {
+    Ok(index) => OpenedIndex::New(index, Some(dir)),
+    // Build the index on every launch if it cannot be saved
+    Err(_) => OpenedIndex::New(Index::create_in_ram(build_schema(locales)), None),
+  }
+}
 
-  let lines = read_lines(CHAR_FILE)?;
-  for line in lines.flatten() {
-    let name: &str = &line[line.char_indices().nth(2).unwrap().0..];
-    if let Some(c) = line.chars().nth(0) {
-      index_writer.add_document(tantivy::doc!(
-        name_field => name,
-        char_field => c.to_string(),
-      ));
+/// Adds a document for each entry. Returns a message for each override file
+/// that could not be read.
+fn add_documents(
+  eng: &SearchEngine,
+  index_writer: &mut IndexWriter,
+) -> tantivy::Result<Vec<String>> {
+  let annotations = cldr::load_annotations(cldr::DEFAULT_LOCALE)?;
+  let mut locale_annotations = vec![];
+  for (locale, name_field) in &eng.locale_fields {
+    let schema = eng.index.schema();
+    let keywords_field = schema.get_field(&locale_keywords_field(locale)).unwrap();
+    let annotations = cldr::load_annotations(locale)?;
+    locale_annotations.push((*name_field, keywords_field, annotations));
+  }
+  let digraphs = digraphs::read_digraphs(digraphs::DIGRAPHS_FILE).unwrap_or_default();
+  let entities = entities::read_entities(entities::ENTITIES_FILE).unwrap_or_default();
+  let mut compose_sequences: HashMap<String, Vec<String>> = HashMap::new();
+  for path in compose::compose_files() {
+    for (text, sequences) in compose::read_compose(path).unwrap_or_default() {
+      let list = compose_sequences.entry(text).or_default();
+      for sequence in sequences {
+        if !list.contains(&sequence) {
+          list.push(sequence);
+        }
+      }
+    }
+  }
+  let mut indexed = HashSet::new();
+  let mut names = HashMap::new();
+  let (entries, load_errors) = layers::load_entries()?;
+  for entry in entries {
+    let mut doc = tantivy::doc!(
+      eng.name_field => entry.name.as_str(),
+      eng.char_field => entry.chars.as_str(),
+    );
+    if let Some(c) = entry.code_point() {
+      doc.add_u64(eng.cp_field, c as u64);
+      names.insert(c, entry.name.clone());
+    }
+    for alias in entry.prop_list("alias") {
+      doc.add_text(eng.alias_field, alias);
+    }
+    for keyword in entry.user_keywords() {
+      doc.add_text(eng.user_keywords_field, keyword);
+    }
+    for tag in entry.prop_list("tags") {
+      doc.add_text(eng.tags_field, tag);
+    }
+    if let Some(annotation) = cldr::find(&annotations, &entry.chars) {
+      for keyword in annotation.tts.iter().chain(&annotation.keywords) {
+        doc.add_text(eng.keywords_field, keyword);
+      }
+    }
+    for (name_field, keywords_field, annotations) in &locale_annotations {
+      if let Some(annotation) = cldr::find(annotations, &entry.chars) {
+        if let Some(tts) = &annotation.tts {
+          doc.add_text(*name_field, tts);
+        }
+        for keyword in &annotation.keywords {
+          doc.add_text(*keywords_field, keyword);
+        }
+      }
+    }
+    if let Some(block) = entry.prop("blk") {
+      doc.add_text(eng.block_field, block);
+    }
+    if let Some(script) = entry.prop("sc") {
+      doc.add_text(eng.script_field, script);
+    }
+    if let Some(gc) = entry.prop("gc") {
+      // The category comes first, then its major class, as in `cat:L`
+      doc.add_text(eng.category_field, gc);
+      if let Some(major) = gc.get(..1).filter(|major| *major != gc) {
+        doc.add_text(eng.category_field, major);
+      }
+      // The long names are matched as words, as in `cat:letter`
+      if let Some((long_name, major_name)) = ucd::category_names(gc) {
+        let names = format!("{} {}", long_name, major_name);
+        doc.add_text(eng.category_name_field, names);
+      }
+    }
+    if let Some(age) = entry.prop("age") {
+      doc.add_text(eng.age_field, age);
+    }
+    for digraph in digraphs.get(&entry.chars).into_iter().flatten() {
+      doc.add_text(eng.digraph_field, digraph);
+    }
+    for entity in entities.get(&entry.chars).into_iter().flatten() {
+      doc.add_text(eng.entity_field, entity);
+    }
+    for sequence in compose_sequences.get(&entry.chars).into_iter().flatten() {
+      doc.add_text(eng.compose_field, sequence);
     }
+    indexed.insert(entry.chars);
+    index_writer.add_document(doc);
   }
+  // Entities such as `&nvlt;` stand for sequences that are not in the
+  // characters file, so they are named after their parts
+  let unindexed: Vec<(&String, &Vec<String>)> = entities
+    .iter()
+    .filter(|(chars, _)| !indexed.contains(*chars))
+    .collect();
+  let mut unnamed: Vec<u32> = unindexed
+    .iter()
+    .flat_map(|(chars, _)| chars.chars())
+    .filter(|c| !names.contains_key(c))
+    .map(|c| c as u32)
+    .collect();
+  unnamed.sort_unstable();
+  unnamed.dedup();
+  let ucd_names = ucd::lookup_properties(&unnamed);
+  for (chars, entity_names) in unindexed {
+    let name = chars
+      .chars()
+      .map(|c| match names.get(&c) {
+        Some(name) => name.clone(),
+        None => match ucd_names.get(&(c as u32)) {
+          Some((name, _)) => name.clone(),
+          None => format!("U+{:04X}", c as u32),
+        },
+      })
+      .collect::<Vec<_>>()
+      .join(" + ");
+    let mut doc = tantivy::doc!(
+      eng.name_field => name,
+      eng.char_field => chars.as_str(),
+    );
+    for entity in entity_names {
+      doc.add_text(eng.entity_field, entity);
+    }
+    index_writer.add_document(doc);
+  }
+  Ok(load_errors)
+}
 
-  index_writer.commit()?;
+pub fn new_query_parser() -> tantivy::Result<SearchEngine> {
+  let locales = cldr::locales();
+  let opened = open_index(&locales);
+  let index = match &opened {
+    OpenedIndex::Cached(index) | OpenedIndex::New(index, _) => index.clone(),
+  };
+  for locale in &locales {
+    index
+      .tokenizers()
+      .register(&locale_tokenizer(locale), locale_analyzer(locale));
+  }
+  let schema = index.schema();
+  let name_field = schema.get_field("name").unwrap();
+  let char_field = schema.get_field("char").unwrap();
+  let cp_field = schema.get_field("cp").unwrap();
+  let alias_field = schema.get_field("alias").unwrap();
+  let keywords_field = schema.get_field("keywords").unwrap();
+  let user_keywords_field = schema.get_field("user_keywords").unwrap();
+  let tags_field = schema.get_field("tags").unwrap();
+  let block_field = schema.get_field("block").unwrap();
+  let script_field = schema.get_field("script").unwrap();
+  let category_field = schema.get_field("category").unwrap();
+  let category_name_field = schema.get_field("category_name").unwrap();
+  let age_field = schema.get_field("age").unwrap();
+  let digraph_field = schema.get_field("digraph").unwrap();
+  let entity_field = schema.get_field("entity").unwrap();
+  let compose_field = schema.get_field("compose").unwrap();
 
   let reader = index
     .reader_builder()
-    .reload_policy(ReloadPolicy::OnCommit)
+    .reload_policy(ReloadPolicy::Manual)
     .try_into()?;
-  let searcher = reader.searcher();
 
-  let query_parser = QueryParser::for_index(&index, vec![name_field]);
-  Ok(SearchEngine {
+  let mut locale_fields = vec![];
+  for locale in &locales {
+    let field = schema.get_field(&locale_name_field(locale)).unwrap();
+    locale_fields.push((locale.clone(), field));
+  }
+
+  // Prefer the words that were added by hand, then characters whose official
+  // name matches
+  let mut default_fields = vec![
+    (user_keywords_field, 1.5),
+    (name_field, 1.0),
+    (alias_field, 0.5),
+    (keywords_field, 0.5),
+    (tags_field, 0.5),
+  ];
+  for (locale, name_field) in &locale_fields {
+    let keywords_field = schema.get_field(&locale_keywords_field(locale)).unwrap();
+    default_fields.extend([(*name_field, 0.75), (keywords_field, 0.5)]);
+  }
+  let mut query_parser =
+    QueryParser::for_index(&index, default_fields.iter().map(|(f, _)| *f).collect());
+  for &(field, boost) in &default_fields {
+    query_parser.set_field_boost(field, boost);
+  }
+  let mut eng = SearchEngine {
     id: rand::random(),
     index: index,
     query_parser: query_parser,
     reader: reader,
     name_field: name_field,
     char_field: char_field,
-  })
+    cp_field: cp_field,
+    alias_field: alias_field,
+    keywords_field: keywords_field,
+    user_keywords_field: user_keywords_field,
+    tags_field: tags_field,
+    block_field: block_field,
+    script_field: script_field,
+    category_field: category_field,
+    category_name_field: category_name_field,
+    age_field: age_field,
+    digraph_field: digraph_field,
+    entity_field: entity_field,
+    compose_field: compose_field,
+    default_fields: default_fields,
+    prefixed_fields: [
+      ("block", block_field),
+      ("blk", block_field),
+      ("script", script_field),
+      ("sc", script_field),
+      ("cat", category_field),
+      ("gc", category_field),
+      ("category", category_field),
+      ("age", age_field),
+      ("name", name_field),
+      ("kw", user_keywords_field),
+      ("keyword", user_keywords_field),
+      ("tag", tags_field),
+      ("tags", tags_field),
+      ("digraph", digraph_field),
+      ("entity", entity_field),
+      ("compose", compose_field),
+    ]
+    .iter()
+    .map(|&(prefix, field)| (prefix.to_string(), field))
+    // A locale searches its localized names, as in `de:pfeil`
+    .chain(locale_fields.iter().cloned())
+    .collect(),
+    locale_fields: locale_fields,
+    load_errors: vec![],
+    exact_fields: vec![
+      ("digraph", digraph_field),
+      ("entity", entity_field),
+      ("compose", compose_field),
+    ],
+  };
+
+  if let OpenedIndex::New(_, dir) = opened {
+    let mut index_writer = eng.index.writer(50_000_000)?;
+    let load_errors = add_documents(&eng, &mut index_writer)?;
+    index_writer.commit()?;
+    index_writer.wait_merging_threads()?;
+    eng.reader.reload()?;
+    // Left incomplete, the index is built again next time so that the
+    // errors are shown until the files are fixed
+    if let (Some(dir), true) = (dir, load_errors.is_empty()) {
+      fs::write(dir.join(INDEX_COMPLETE_FILE), "")?;
+    }
+    eng.load_errors = load_errors;
+  }
+  Ok(eng)
+}
+
+fn query_words(query: &str) -> Vec<String> {
+  query
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|w| !w.is_empty() && !["AND", "OR", "NOT"].contains(w))
+    .map(|w| w.to_lowercase())
+    .collect()
+}
+
+fn count_matching_words(words: &[String], text: &str) -> usize {
+  let text_words: Vec<String> = text
+    .split(|c: char| !c.is_alphanumeric())
+    .map(|w| w.to_lowercase())
+    .collect();
+  words
+    .iter()
+    .filter(|w| text_words.iter().any(|tw| tw.starts_with(w.as_str())))
+    .count()
+}
+
+/// The alias that matches more of the query than the official name, to show
+/// why a result was found
+pub fn matched_alias<'a>(
+  query: &str,
+  name: &str,
+  aliases: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+  let words = query_words(query);
+  let name_count = count_matching_words(&words, name);
+  aliases
+    .into_iter()
+    .map(|alias| (count_matching_words(&words, alias), alias))
+    .filter(|(count, _)| *count > name_count)
+    .max_by_key(|(count, _)| *count)
+    .map(|(_, alias)| alias)
+}
+
+pub struct CodePointQuery {
+  pub ch: char,
+  /// Written with a prefix such as `U+`, rather than as bare hex digits that
+  /// could also be a word
+  pub explicit: bool,
+}
+
+/// Recognises `U+2192`, `0x2192`, `\u2192`, `\u{2192}`, `&#x2192;`, `&#8594;`
+/// and bare hex such as `2192`
+pub fn parse_code_point(s: &str) -> Option<CodePointQuery> {
+  let lower = s.trim().to_ascii_lowercase();
+  let hex_prefixes = ["u+", "0x", "\\u{", "\\u", "&#x"];
+  let (digits, radix, explicit) =
+    if let Some(p) = hex_prefixes.iter().find(|p| lower.starts_with(*p)) {
+      (&lower[p.len()..], 16, true)
+    } else if let Some(dec) = lower.strip_prefix("&#") {
+      (dec, 10, true)
+    } else if (4..=6).contains(&lower.len()) && lower.chars().any(|c| c.is_ascii_digit()) {
+      (&lower[..], 16, false)
+    } else {
+      return None;
+    };
+  let digits = digits.trim_end_matches(|c| c == ';' || c == '}');
+  if digits.is_empty() || digits.len() > 7 {
+    return None;
+  }
+  let cp = u32::from_str_radix(digits, radix).ok()?;
+  char::from_u32(cp).map(|ch| CodePointQuery { ch, explicit })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn props(entry: &Entry) -> Vec<(&str, &str)> {
+    entry
+      .props
+      .iter()
+      .map(|(k, v)| (k.as_str(), v.as_str()))
+      .collect()
+  }
+
+  #[test]
+  fn parses_name_keywords_and_properties() {
+    let entry =
+      parse_line("⇒ RIGHTWARDS DOUBLE ARROW | implies, then ; cp=U+21D2 ; gc=Sm").unwrap();
+    assert_eq!(entry.chars, "⇒");
+    assert_eq!(entry.name, "RIGHTWARDS DOUBLE ARROW");
+    assert_eq!(entry.keywords, vec!["implies", "then"]);
+    assert_eq!(props(&entry), vec![("cp", "U+21D2"), ("gc", "Sm")]);
+  }
+
+  #[test]
+  fn parses_sequences_and_spaces() {
+    let entry = parse_line("👍🏽 thumbs up: medium skin tone").unwrap();
+    assert_eq!(entry.chars, "👍🏽");
+    assert_eq!(entry.name, "thumbs up: medium skin tone");
+    let entry = parse_line("\u{a0} NO-BREAK SPACE ; alias=NBSP").unwrap();
+    assert_eq!(entry.chars, "\u{a0}");
+    assert_eq!(entry.name, "NO-BREAK SPACE");
+    assert_eq!(entry.prop_list("alias").collect::<Vec<_>>(), vec!["NBSP"]);
+  }
+
+  #[test]
+  fn description_can_be_left_out() {
+    let entry = parse_line("Δ | change").unwrap();
+    assert_eq!(entry.name, "");
+    assert_eq!(entry.keywords, vec!["change"]);
+    let entry = parse_line("Δ ; kw=change, other").unwrap();
+    assert_eq!(entry.name, "");
+    assert_eq!(
+      entry.user_keywords().collect::<Vec<_>>(),
+      vec!["change", "other"]
+    );
+  }
+
+  #[test]
+  fn skips_blank_lines_and_comments() {
+    assert!(parse_line("").is_none());
+    assert!(parse_line("   ").is_none());
+    assert!(parse_line("\r").is_none());
+    assert!(parse_line("# arrows").is_none());
+  }
+
+  #[test]
+  fn backslash_escapes_special_starts() {
+    let entry = parse_line("\\# NUMBER SIGN").unwrap();
+    assert_eq!(entry.chars, "#");
+    assert_eq!(entry.name, "NUMBER SIGN");
+    let entry = parse_line("\\ REVERSE SOLIDUS").unwrap();
+    assert_eq!(entry.chars, "\\");
+    assert_eq!(escape_chars("#"), "\\#");
+    assert_eq!(escape_chars("[x]"), "\\[x]");
+    assert_eq!(escape_chars("Δ"), "Δ");
+  }
+
+  #[test]
+  fn ignores_crlf_line_endings() {
+    let entry = parse_line("Δ GREEK CAPITAL LETTER DELTA ; gc=Lu\r").unwrap();
+    assert_eq!(entry.name, "GREEK CAPITAL LETTER DELTA");
+    assert_eq!(props(&entry), vec![("gc", "Lu")]);
+    let entry = parse_line("Δ\r").unwrap();
+    assert_eq!(entry.chars, "Δ");
+  }
+
+  #[test]
+  fn parses_sections() {
+    assert_eq!(parse_section("[Logic]"), Some("Logic"));
+    assert_eq!(parse_section("[Math arrows]\r"), Some("Math arrows"));
+    assert_eq!(parse_section("[ LEFT SQUARE BRACKET ]"), None);
+    assert_eq!(parse_section("[]"), None);
+    assert_eq!(parse_section("[ LEFT SQUARE BRACKET"), None);
+  }
+
+  #[test]
+  fn formats_lines_that_parse_back() {
+    let line = "\\# NUMBER SIGN | hash ; gc=Po ; tags=ascii";
+    assert_eq!(format_line(&parse_line(line).unwrap()), line);
+    let line = "Δ | change";
+    assert_eq!(format_line(&parse_line(line).unwrap()), line);
+  }
+
+  #[test]
+  fn parses_code_points() {
+    for s in [
+      "U+2192",
+      "u+2192",
+      "0x2192",
+      "\\u2192",
+      "\\u{2192}",
+      "&#x2192;",
+      "&#8594;",
+    ] {
+      let cp = parse_code_point(s).unwrap();
+      assert_eq!(cp.ch, '→', "{}", s);
+      assert!(cp.explicit, "{}", s);
+    }
+    let cp = parse_code_point("2192").unwrap();
+    assert_eq!(cp.ch, '→');
+    assert!(!cp.explicit);
+  }
+
+  #[test]
+  fn rejects_words_and_invalid_code_points() {
+    assert!(parse_code_point("arrow").is_none());
+    assert!(parse_code_point("cafe").is_none());
+    assert!(parse_code_point("123").is_none());
+    assert!(parse_code_point("U+").is_none());
+    assert!(parse_code_point("U+D800").is_none());
+    assert!(parse_code_point("U+110000").is_none());
+  }
 }
@@ -0,0 +1,440 @@
+use crate::layers;
+use crate::search::{self, Entry};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files that hold character sets in another format than
+/// the characters file
+pub const SET_EXTENSIONS: [&str; 3] = ["json", "toml", "csv"];
+
+/// Columns of a CSV file, besides those holding properties
+const CSV_COLUMNS: [&str; 4] = ["chars", "name", "keywords", "tags"];
+
+/// A character or sequence in a JSON, TOML or CSV set
+#[derive(Serialize, Deserialize)]
+struct Record {
+  chars: String,
+  #[serde(default)]
+  name: String,
+  #[serde(default)]
+  keywords: Vec<String>,
+  #[serde(default)]
+  tags: Vec<String>,
+  /// Other properties, such as `gc` or `blk`
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  properties: BTreeMap<String, String>,
+}
+
+/// A TOML set, written as a `[[characters]]` table for each record
+#[derive(Serialize, Deserialize)]
+struct TomlSet {
+  characters: Vec<Record>,
+}
+
+impl From<Record> for Entry {
+  fn from(record: Record) -> Entry {
+    let mut props: Vec<(String, String)> = record.properties.into_iter().collect();
+    if !record.tags.is_empty() {
+      props.push(("tags".to_string(), record.tags.join(", ")));
+    }
+    Entry {
+      chars: record.chars,
+      name: record.name,
+      keywords: record.keywords,
+      props,
+    }
+  }
+}
+
+impl From<&Entry> for Record {
+  fn from(entry: &Entry) -> Record {
+    Record {
+      chars: entry.chars.clone(),
+      name: entry.name.clone(),
+      keywords: entry.user_keywords().map(str::to_string).collect(),
+      tags: entry.prop_list("tags").map(str::to_string).collect(),
+      properties: entry
+        .props
+        .iter()
+        .filter(|(key, _)| key != "kw" && key != "tags")
+        .cloned()
+        .collect(),
+    }
+  }
+}
+
+fn extension(path: &Path) -> Option<String> {
+  path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .map(str::to_ascii_lowercase)
+}
+
+pub fn is_set_file(path: &Path) -> bool {
+  extension(path).map_or(false, |ext| SET_EXTENSIONS.contains(&ext.as_str()))
+}
+
+fn invalid_data<E>(e: E) -> io::Error
+where
+  E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+  io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// A comma-separated list in a CSV cell
+fn split_list(s: &str) -> Vec<String> {
+  s.split(',')
+    .map(str::trim)
+    .filter(|v| !v.is_empty())
+    .map(str::to_string)
+    .collect()
+}
+
+/// Reads a CSV file with a header row. `chars` is required, and columns other
+/// than `name`, `keywords` and `tags` are read as properties.
+fn read_csv(path: &Path) -> io::Result<Vec<Record>> {
+  let mut reader = csv::Reader::from_path(path)?;
+  // Spreadsheets often save CSV files with a byte order mark, and column
+  // names such as `Chars` are read in any case
+  let headers: Vec<String> = reader
+    .headers()?
+    .iter()
+    .enumerate()
+    .map(|(i, h)| {
+      let h = if i == 0 {
+        h.trim_start_matches('\u{feff}')
+      } else {
+        h
+      };
+      match CSV_COLUMNS.iter().find(|c| c.eq_ignore_ascii_case(h)) {
+        Some(column) => column.to_string(),
+        None => h.to_string(),
+      }
+    })
+    .collect();
+  if !headers.iter().any(|h| h == "chars") {
+    return Err(invalid_data("missing chars column"));
+  }
+  let mut records = vec![];
+  for row in reader.records() {
+    let row = row?;
+    let mut record = Record {
+      chars: String::new(),
+      name: String::new(),
+      keywords: vec![],
+      tags: vec![],
+      properties: BTreeMap::new(),
+    };
+    for (header, value) in headers.iter().zip(row.iter()) {
+      match header.as_str() {
+        "chars" => record.chars = value.to_string(),
+        "name" => record.name = value.trim().to_string(),
+        "keywords" => record.keywords = split_list(value),
+        "tags" => record.tags = split_list(value),
+        _ if !value.is_empty() => {
+          record.properties.insert(header.clone(), value.to_string());
+        }
+        _ => {}
+      }
+    }
+    records.push(record);
+  }
+  Ok(records)
+}
+
+fn write_csv(path: &Path, records: &[Record]) -> io::Result<()> {
+  // Keywords share a cell, separated by commas
+  if let Some(record) = records
+    .iter()
+    .find(|r| r.keywords.iter().any(|k| k.contains(',')))
+  {
+    return Err(invalid_data(format!(
+      "{} cannot be written to a CSV file: `,` in a keyword",
+      record.chars
+    )));
+  }
+  let mut keys: Vec<&String> = records.iter().flat_map(|r| r.properties.keys()).collect();
+  keys.sort();
+  keys.dedup();
+  let mut writer = csv::Writer::from_path(path)?;
+  let headers = CSV_COLUMNS
+    .iter()
+    .copied()
+    .chain(keys.iter().map(|k| k.as_str()));
+  writer.write_record(headers)?;
+  for record in records {
+    let mut row = vec![
+      record.chars.clone(),
+      record.name.clone(),
+      record.keywords.join(", "),
+      record.tags.join(", "),
+    ];
+    for key in &keys {
+      row.push(record.properties.get(*key).cloned().unwrap_or_default());
+    }
+    writer.write_record(&row)?;
+  }
+  writer.flush()
+}
+
+/// Fails for an entry that would not read back the same from a line of the
+/// characters file, which cannot escape the separators between its parts
+fn check_line(entry: &Entry) -> io::Result<()> {
+  let separators = |s: &str| s.contains(" | ") || s.contains(" ; ");
+  let problem = if entry.chars.contains(' ') {
+    "a space in the characters"
+  } else if separators(&entry.name) || entry.name.starts_with("| ") || entry.name.starts_with("; ")
+  {
+    "` | ` or ` ; ` in the name"
+  } else if entry
+    .keywords
+    .iter()
+    .any(|k| k.contains(',') || separators(k))
+  {
+    "`,` or ` ; ` in a keyword"
+  } else if entry
+    .props
+    .iter()
+    .any(|(key, value)| key.contains('=') || separators(key) || separators(value))
+  {
+    "`=` or ` ; ` in a property"
+  } else {
+    return Ok(());
+  };
+  Err(invalid_data(format!(
+    "{} cannot be written to a characters file: {}",
+    entry.chars, problem
+  )))
+}
+
+/// Reads a character set from a `.json`, `.toml` or `.csv` file
+pub fn read_set(path: &Path) -> io::Result<Vec<Entry>> {
+  let records = match extension(path).as_deref() {
+    Some("json") => serde_json::from_str(&fs::read_to_string(path)?)?,
+    Some("toml") => {
+      let set: TomlSet = toml::from_str(&fs::read_to_string(path)?).map_err(invalid_data)?;
+      set.characters
+    }
+    Some("csv") => read_csv(path)?,
+    _ => return Err(invalid_data("not a .json, .toml or .csv file")),
+  };
+  Ok(
+    records
+      .into_iter()
+      .filter(|record| !record.chars.is_empty())
+      .map(Entry::from)
+      .collect(),
+  )
+}
+
+/// Writes entries to a `.json`, `.toml` or `.csv` file, or else in the
+/// format of the characters file
+pub fn write_set(path: &Path, entries: &[Entry]) -> io::Result<()> {
+  let records: Vec<Record> = entries.iter().map(Record::from).collect();
+  match extension(path).as_deref() {
+    Some("json") => fs::write(path, serde_json::to_string_pretty(&records)?),
+    Some("toml") => {
+      let set = TomlSet {
+        characters: records,
+      };
+      fs::write(path, toml::to_string(&set).map_err(invalid_data)?)
+    }
+    Some("csv") => write_csv(path, &records),
+    _ => {
+      for entry in entries {
+        check_line(entry)?;
+      }
+      let mut file = io::BufWriter::new(File::create(path)?);
+      for entry in entries {
+        writeln!(file, "{}", search::format_line(entry))?;
+      }
+      file.flush()
+    }
+  }
+}
+
+pub struct ExportOptions {
+  pub out: PathBuf,
+  /// The file to export instead of all the characters files merged
+  pub from: Option<PathBuf>,
+  /// If not empty, only characters with one of these tags are exported
+  pub tags: Vec<String>,
+}
+
+impl ExportOptions {
+  pub fn from_args(args: &[String]) -> Result<Self, String> {
+    let mut out = None;
+    let mut opts = ExportOptions {
+      out: PathBuf::new(),
+      from: None,
+      tags: vec![],
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      let mut value = || {
+        args
+          .next()
+          .cloned()
+          .ok_or_else(|| format!("missing value for {}", arg))
+      };
+      match arg.as_str() {
+        "--from" => opts.from = Some(PathBuf::from(value()?)),
+        "--tag" => opts.tags.push(value()?),
+        _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+        _ if out.is_none() => out = Some(PathBuf::from(arg)),
+        _ => return Err(format!("unexpected argument: {}", arg)),
+      }
+    }
+    opts.out = out.ok_or_else(|| "missing output file".to_string())?;
+    Ok(opts)
+  }
+}
+
+/// Writes the characters to the output file in the format given by its
+/// extension. Returns the number of characters written.
+pub fn export(opts: &ExportOptions) -> io::Result<usize> {
+  let mut entries = match &opts.from {
+    Some(path) => layers::load_file(path)?,
+    None => {
+      // Leaving out a set that could not be read would export it incompletely
+      let (entries, errors) = layers::load_entries()?;
+      if !errors.is_empty() {
+        return Err(invalid_data(errors.join("; ")));
+      }
+      entries
+    }
+  };
+  if !opts.tags.is_empty() {
+    entries.retain(|entry| {
+      entry
+        .prop_list("tags")
+        .any(|tag| opts.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    });
+  }
+  write_set(&opts.out, &entries)?;
+  Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tempdir::TempDir;
+
+  fn entries() -> Vec<Entry> {
+    [
+      "⇒ RIGHTWARDS DOUBLE ARROW | implies, then ; cp=U+21D2 ; gc=Sm ; tags=Logic",
+      "\\# NUMBER SIGN ; tags=ascii, Logic",
+      "★ | star",
+    ]
+    .iter()
+    .map(|line| search::parse_line(line).unwrap())
+    .collect()
+  }
+
+  /// Writes the entries to a file with the extension and reads them back
+  fn round_trip(extension: &str, entries: &[Entry]) -> io::Result<Vec<Entry>> {
+    let dir = TempDir::new(&format!("round-trip-{}", extension));
+    let path = dir.path().join(format!("set.{}", extension));
+    write_set(&path, entries)?;
+    if is_set_file(&path) {
+      read_set(&path)
+    } else {
+      layers::load_file(&path)
+    }
+  }
+
+  #[test]
+  fn sets_round_trip() {
+    // Properties of sets are ordered by key, and tags come last
+    let expected = vec![
+      "⇒ RIGHTWARDS DOUBLE ARROW | implies, then ; cp=U+21D2 ; gc=Sm ; tags=Logic",
+      "\\# NUMBER SIGN ; tags=ascii, Logic",
+      "★ | star",
+    ];
+    for extension in ["json", "toml", "csv", "txt"] {
+      let read = round_trip(extension, &entries()).unwrap();
+      let lines: Vec<String> = read.iter().map(search::format_line).collect();
+      assert_eq!(lines, expected, "{}", extension);
+    }
+
+    // The characters file cannot hold its separators in a name or keyword,
+    // and a CSV cell cannot hold a keyword with a comma
+    let entry = |name: &str, keyword: &str| Entry {
+      chars: "⊕".to_string(),
+      name: name.to_string(),
+      keywords: vec![keyword.to_string()],
+      props: vec![],
+    };
+    let cases = [
+      (
+        entry("CIRCLED PLUS | XOR ; OR", "xor"),
+        &["json", "toml", "csv"][..],
+      ),
+      (
+        entry("CIRCLED PLUS", "exclusive, or"),
+        &["json", "toml"][..],
+      ),
+    ];
+    for (written, readable) in &cases {
+      for extension in ["json", "toml", "csv", "txt"] {
+        match round_trip(extension, std::slice::from_ref(written)) {
+          Ok(read) if readable.contains(&extension) => {
+            assert_eq!(read[0].name, written.name, "{}", extension);
+            assert_eq!(read[0].keywords, written.keywords, "{}", extension);
+          }
+          Ok(_) => panic!("{} should not be written to {}", written.name, extension),
+          Err(e) => {
+            assert!(!readable.contains(&extension), "{}: {}", extension, e);
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn reads_json_with_only_some_fields() {
+    let dir = TempDir::new("partial");
+    let path = dir.write(
+      "set.json",
+      r#"[{"chars": "✓", "keywords": ["tick"]}, {"chars": ""}]"#,
+    );
+    let read = read_set(&path).unwrap();
+    assert_eq!(
+      read.iter().map(search::format_line).collect::<Vec<_>>(),
+      vec!["✓ | tick"]
+    );
+  }
+
+  #[test]
+  fn reads_csv_with_a_byte_order_mark() {
+    let dir = TempDir::new("bom");
+    let path = dir.write("set.csv", "\u{feff}chars,name\r\n✓,CHECK MARK\r\n");
+    let read = read_set(&path).unwrap();
+    assert_eq!(
+      read.iter().map(search::format_line).collect::<Vec<_>>(),
+      vec!["✓ CHECK MARK"]
+    );
+  }
+
+  #[test]
+  fn reads_csv_headers_in_any_case() {
+    let dir = TempDir::new("header-case");
+    let path = dir.write("set.csv", "Chars,NAME,Keywords,gc\n✓,CHECK MARK,tick,So\n");
+    let read = read_set(&path).unwrap();
+    assert_eq!(
+      read.iter().map(search::format_line).collect::<Vec<_>>(),
+      vec!["✓ CHECK MARK | tick ; gc=So"]
+    );
+  }
+
+  #[test]
+  fn csv_needs_a_chars_column() {
+    let dir = TempDir::new("no-chars");
+    let path = dir.write("set.csv", "name\nCHECK MARK\n");
+    assert!(read_set(&path).is_err());
+  }
+}
@@ -0,0 +1,12 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "character-picker";
+
+/// Per-user folder for files that the picker writes, such as the cached search index
+pub fn data_dir() -> PathBuf {
+  env::var_os("LOCALAPPDATA")
+    .map(PathBuf::from)
+    .unwrap_or_else(env::temp_dir)
+    .join(APP_DIR)
+}
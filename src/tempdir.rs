@@ -0,0 +1,32 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A new folder in the temporary folder for a test, deleted with everything in
+/// it when dropped, even if the test fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  /// The name must be unique among the tests, which run at the same time
+  pub fn new(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("picker-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.0
+  }
+
+  /// Writes a file in the folder and returns its path
+  pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+    let path = self.0.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
@@ -0,0 +1,602 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const UCD_DIR: &str = "ucd";
+
+pub const UNICODE_DATA_FILE: &str = "UnicodeData.txt";
+pub const DERIVED_NAME_FILE: &str = "DerivedName.txt";
+pub const BLOCKS_FILE: &str = "Blocks.txt";
+pub const SCRIPTS_FILE: &str = "Scripts.txt";
+pub const NAME_ALIASES_FILE: &str = "NameAliases.txt";
+pub const NAMES_LIST_FILE: &str = "NamesList.txt";
+pub const AGE_FILE: &str = "DerivedAge.txt";
+pub const CONFUSABLES_FILE: &str = "confusables.txt";
+pub const EMOJI_SEQUENCE_FILES: [&str; 2] = ["emoji-sequences.txt", "emoji-zwj-sequences.txt"];
+
+/// One line of UnicodeData.txt
+pub struct Record {
+  pub cp: u32,
+  pub name: String,
+  pub gc: String,
+  /// Code points the character decomposes to, starting with a `<tag>` if
+  /// the decomposition is only a compatibility one
+  pub decomposition: String,
+  /// Simple uppercase, lowercase and titlecase mappings
+  pub case_mappings: Vec<u32>,
+}
+
+/// Emoji made of several code points, such as a flag or a keycap
+pub struct Sequence {
+  pub cps: Vec<u32>,
+  pub name: String,
+}
+
+const GENERAL_CATEGORIES: [(&str, &str); 30] = [
+  ("Lu", "Uppercase_Letter"),
+  ("Ll", "Lowercase_Letter"),
+  ("Lt", "Titlecase_Letter"),
+  ("Lm", "Modifier_Letter"),
+  ("Lo", "Other_Letter"),
+  ("Mn", "Nonspacing_Mark"),
+  ("Mc", "Spacing_Mark"),
+  ("Me", "Enclosing_Mark"),
+  ("Nd", "Decimal_Number"),
+  ("Nl", "Letter_Number"),
+  ("No", "Other_Number"),
+  ("Pc", "Connector_Punctuation"),
+  ("Pd", "Dash_Punctuation"),
+  ("Ps", "Open_Punctuation"),
+  ("Pe", "Close_Punctuation"),
+  ("Pi", "Initial_Punctuation"),
+  ("Pf", "Final_Punctuation"),
+  ("Po", "Other_Punctuation"),
+  ("Sm", "Math_Symbol"),
+  ("Sc", "Currency_Symbol"),
+  ("Sk", "Modifier_Symbol"),
+  ("So", "Other_Symbol"),
+  ("Zs", "Space_Separator"),
+  ("Zl", "Line_Separator"),
+  ("Zp", "Paragraph_Separator"),
+  ("Cc", "Control"),
+  ("Cf", "Format"),
+  ("Cs", "Surrogate"),
+  ("Co", "Private_Use"),
+  ("Cn", "Unassigned"),
+];
+
+const MAJOR_CATEGORIES: [(&str, &str); 7] = [
+  ("L", "Letter"),
+  ("M", "Mark"),
+  ("N", "Number"),
+  ("P", "Punctuation"),
+  ("S", "Symbol"),
+  ("Z", "Separator"),
+  ("C", "Other"),
+];
+
+/// The general category or major class written with its usual case, as in
+/// `Lu` for `lu`
+pub fn category_code(s: &str) -> Option<&'static str> {
+  GENERAL_CATEGORIES
+    .iter()
+    .chain(&MAJOR_CATEGORIES)
+    .map(|(short, _)| *short)
+    .find(|short| short.eq_ignore_ascii_case(s))
+}
+
+/// Long names of a general category and its major class, like
+/// `("Uppercase_Letter", "Letter")` for `Lu`
+pub fn category_names(gc: &str) -> Option<(&'static str, &'static str)> {
+  let long_name = GENERAL_CATEGORIES.iter().find(|(short, _)| *short == gc)?.1;
+  let major = MAJOR_CATEGORIES
+    .iter()
+    .find(|(short, _)| gc.starts_with(short))?
+    .1;
+  Some((long_name, major))
+}
+
+/// Property value that applies to a range of code points, as in Blocks.txt
+pub struct RangeValue {
+  pub first: u32,
+  pub last: u32,
+  pub value: String,
+}
+
+/// Semicolon-separated fields of each data line, with comments and blank lines removed
+pub fn data_lines<P: AsRef<Path>>(path: P) -> io::Result<impl Iterator<Item = Vec<String>>> {
+  let file = File::open(path)?;
+  Ok(
+    io::BufReader::new(file)
+      .lines()
+      .flatten()
+      .filter_map(|line| {
+        let data = match line.find('#') {
+          Some(i) => &line[..i],
+          None => &line[..],
+        };
+        if data.trim().is_empty() {
+          None
+        } else {
+          Some(data.split(';').map(|s| s.trim().to_string()).collect())
+        }
+      }),
+  )
+}
+
+pub fn parse_cp(s: &str) -> Option<u32> {
+  u32::from_str_radix(s.trim(), 16).ok()
+}
+
+/// Parses a sequence of code points such as `0072 006E`
+pub fn parse_chars(s: &str) -> Option<String> {
+  s.split_whitespace()
+    .map(|cp| parse_cp(cp).and_then(char::from_u32))
+    .collect()
+}
+
+/// Parses `XXXX` or `XXXX..YYYY`, where each code point may also be written
+/// as `U+XXXX`
+pub fn parse_cp_range(s: &str) -> Option<(u32, u32)> {
+  let cp = |s: &str| {
+    let s = s.trim();
+    parse_cp(
+      s.strip_prefix("U+")
+        .or_else(|| s.strip_prefix("u+"))
+        .unwrap_or(s),
+    )
+  };
+  match s.split_once("..") {
+    Some((first, last)) => Some((cp(first)?, cp(last)?)),
+    None => cp(s).map(|c| (c, c)),
+  }
+}
+
+pub fn read_unicode_data<P: AsRef<Path>>(path: P) -> io::Result<Vec<Record>> {
+  let mut records = vec![];
+  for fields in data_lines(path)? {
+    if fields.len() < 3 {
+      continue;
+    }
+    if let Some(cp) = parse_cp(&fields[0]) {
+      records.push(Record {
+        cp,
+        name: fields[1].clone(),
+        gc: fields[2].clone(),
+        decomposition: fields.get(5).cloned().unwrap_or_default(),
+        case_mappings: fields
+          .iter()
+          .skip(12)
+          .filter_map(|f| parse_cp(f))
+          .filter(|&mapping| mapping != cp)
+          .collect(),
+      });
+    }
+  }
+  Ok(records)
+}
+
+/// Names of individual code points. Ranges named with a trailing `*` are
+/// only included when `expand_ranges` is set.
+pub fn read_derived_names<P: AsRef<Path>>(
+  path: P,
+  expand_ranges: bool,
+) -> io::Result<HashMap<u32, String>> {
+  let mut names = HashMap::default();
+  for fields in data_lines(path)? {
+    if fields.len() < 2 {
+      continue;
+    }
+    if let Some((first, last)) = parse_cp_range(&fields[0]) {
+      let name = &fields[1];
+      if let Some(prefix) = name.strip_suffix('*') {
+        if expand_ranges {
+          for cp in first..=last {
+            names.insert(cp, format!("{}{:04X}", prefix, cp));
+          }
+        }
+      } else if first == last {
+        names.insert(first, name.clone());
+      }
+    }
+  }
+  Ok(names)
+}
+
+/// Reads a file with `XXXX..YYYY ; value` lines such as Blocks.txt or Scripts.txt
+pub fn read_ranges<P: AsRef<Path>>(path: P) -> io::Result<Vec<RangeValue>> {
+  let mut ranges = vec![];
+  for fields in data_lines(path)? {
+    if fields.len() < 2 {
+      continue;
+    }
+    if let Some((first, last)) = parse_cp_range(&fields[0]) {
+      ranges.push(RangeValue {
+        first,
+        last,
+        value: fields[1].clone(),
+      });
+    }
+  }
+  ranges.sort_by_key(|r| r.first);
+  Ok(ranges)
+}
+
+/// Looks up the value for a code point in ranges sorted by `read_ranges`
+pub fn range_value(ranges: &[RangeValue], cp: u32) -> Option<&str> {
+  let idx = ranges.partition_point(|r| r.first <= cp);
+  if idx == 0 {
+    return None;
+  }
+  let r = &ranges[idx - 1];
+  if cp <= r.last {
+    Some(&r.value)
+  } else {
+    None
+  }
+}
+
+/// Formal aliases such as corrections and abbreviations from NameAliases.txt
+pub fn read_name_aliases<P: AsRef<Path>>(path: P) -> io::Result<HashMap<u32, Vec<String>>> {
+  let mut aliases: HashMap<u32, Vec<String>> = HashMap::default();
+  for fields in data_lines(path)? {
+    if fields.len() < 2 {
+      continue;
+    }
+    if let Some(cp) = parse_cp(&fields[0]) {
+      aliases.entry(cp).or_default().push(fields[1].clone());
+    }
+  }
+  Ok(aliases)
+}
+
+/// Informal aliases, the `= ...` lines under each character in NamesList.txt
+pub fn read_informal_aliases<P: AsRef<Path>>(path: P) -> io::Result<HashMap<u32, Vec<String>>> {
+  let file = File::open(path)?;
+  let mut aliases: HashMap<u32, Vec<String>> = HashMap::default();
+  let mut cp = None;
+  for line in io::BufReader::new(file).lines().flatten() {
+    if let Some(alias) = line.strip_prefix("\t= ") {
+      if let Some(cp) = cp {
+        let list = aliases.entry(cp).or_default();
+        list.extend(alias.split(", ").map(|a| a.trim().to_string()));
+      }
+    } else if !line.starts_with('\t') {
+      cp = line.split_once('\t').and_then(|(cp, _)| parse_cp(cp));
+    }
+  }
+  Ok(aliases)
+}
+
+/// Replaces escapes like `\x{23}` used in the names of keycap sequences
+fn unescape_name(name: &str) -> String {
+  let mut out = String::new();
+  let mut rest = name;
+  while let Some(i) = rest.find("\\x{") {
+    out.push_str(&rest[..i]);
+    rest = &rest[i + 3..];
+    match rest.find('}') {
+      Some(end) => {
+        out.extend(parse_cp(&rest[..end]).and_then(char::from_u32));
+        rest = &rest[end + 1..];
+      }
+      None => break,
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+/// Reads the multi-code-point lines of emoji-sequences.txt or emoji-zwj-sequences.txt
+pub fn read_sequences<P: AsRef<Path>>(path: P) -> io::Result<Vec<Sequence>> {
+  let mut sequences = vec![];
+  for fields in data_lines(path)? {
+    if fields.len() < 3 {
+      continue;
+    }
+    let cps: Option<Vec<u32>> = fields[0].split_whitespace().map(parse_cp).collect();
+    match cps {
+      Some(cps) if cps.len() > 1 => sequences.push(Sequence {
+        cps,
+        name: unescape_name(&fields[2]).to_uppercase(),
+      }),
+      _ => (),
+    }
+  }
+  Ok(sequences)
+}
+
+/// Scans the UCD files for the name of a single code point, for characters
+/// that are missing from the characters file
+pub fn lookup_name(cp: u32) -> Option<String> {
+  let dir = Path::new(UCD_DIR);
+  if let Ok(lines) = data_lines(dir.join(DERIVED_NAME_FILE)) {
+    for fields in lines {
+      match parse_cp_range(&fields[0]) {
+        Some((first, last)) if first <= cp && cp <= last && fields.len() > 1 => {
+          return Some(match fields[1].strip_suffix('*') {
+            Some(prefix) => format!("{}{:04X}", prefix, cp),
+            None => fields[1].clone(),
+          });
+        }
+        _ => (),
+      }
+    }
+  }
+  for fields in data_lines(dir.join(UNICODE_DATA_FILE)).ok()? {
+    if fields.len() > 10 && parse_cp(&fields[0]) == Some(cp) {
+      // Controls are only named by their old Unicode 1.0 name
+      if fields[1].starts_with('<') && !fields[10].is_empty() {
+        return Some(fields[10].clone());
+      }
+      return Some(fields[1].clone());
+    }
+  }
+  None
+}
+
+/// Name and general category of each code point, read from UnicodeData.txt
+/// in one pass. Unassigned code points are left out.
+pub fn lookup_properties(cps: &[u32]) -> HashMap<u32, (String, String)> {
+  let mut found = HashMap::new();
+  let lines = match data_lines(Path::new(UCD_DIR).join(UNICODE_DATA_FILE)) {
+    Ok(lines) => lines,
+    Err(_) => return found,
+  };
+  let mut range_first = None;
+  for fields in lines {
+    let cp = match parse_cp(&fields[0]) {
+      Some(cp) if fields.len() > 10 => cp,
+      _ => continue,
+    };
+    if fields[1].ends_with(", First>") {
+      range_first = Some(cp);
+      continue;
+    }
+    let first = if fields[1].ends_with(", Last>") {
+      range_first.take().unwrap_or(cp)
+    } else {
+      cp
+    };
+    for &target in cps {
+      if target < first || cp < target || found.contains_key(&target) {
+        continue;
+      }
+      let name = if first != cp {
+        lookup_name(target).unwrap_or_default()
+      } else if fields[1].starts_with('<') && !fields[10].is_empty() {
+        fields[10].clone()
+      } else {
+        fields[1].clone()
+      };
+      found.insert(target, (name, fields[2].clone()));
+    }
+  }
+  found
+}
+
+pub struct GenOptions {
+  pub ucd_dir: PathBuf,
+  pub out: PathBuf,
+  /// Only code points inside one of these inclusive ranges are written
+  pub ranges: Vec<(u32, u32)>,
+  /// If not empty, only characters in these blocks are written
+  pub blocks: Vec<String>,
+  pub exclude_blocks: Vec<String>,
+  /// Include characters such as CJK ideographs whose names are derived from their code point
+  pub ideographs: bool,
+}
+
+impl Default for GenOptions {
+  fn default() -> Self {
+    GenOptions {
+      ucd_dir: PathBuf::from(UCD_DIR),
+      out: PathBuf::from(crate::search::CHAR_FILE),
+      // Leave out the printable ASCII characters, which can be typed directly
+      ranges: vec![(127, 0x10FFFF)],
+      blocks: vec![],
+      exclude_blocks: vec![],
+      ideographs: false,
+    }
+  }
+}
+
+impl GenOptions {
+  pub fn from_args(args: &[String]) -> Result<Self, String> {
+    let mut opts = GenOptions::default();
+    let mut custom_ranges = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      let mut value = || {
+        args
+          .next()
+          .cloned()
+          .ok_or_else(|| format!("missing value for {}", arg))
+      };
+      match arg.as_str() {
+        "--ucd" => opts.ucd_dir = PathBuf::from(value()?),
+        "--out" => opts.out = PathBuf::from(value()?),
+        "--range" => {
+          let s = value()?;
+          let range =
+            parse_cp_range(&s).ok_or_else(|| format!("invalid code point range: {}", s))?;
+          custom_ranges.push(range);
+        }
+        "--block" => opts.blocks.push(value()?),
+        "--exclude-block" => opts.exclude_blocks.push(value()?),
+        "--ideographs" => opts.ideographs = true,
+        _ => return Err(format!("unknown option: {}", arg)),
+      }
+    }
+    if !custom_ranges.is_empty() {
+      opts.ranges = custom_ranges;
+    }
+    Ok(opts)
+  }
+
+  fn includes(&self, cp: u32, block: Option<&str>) -> bool {
+    let in_blocks =
+      |names: &[String]| block.map_or(false, |b| names.iter().any(|n| n.eq_ignore_ascii_case(b)));
+    self
+      .ranges
+      .iter()
+      .any(|(first, last)| *first <= cp && cp <= *last)
+      && (self.blocks.is_empty() || in_blocks(&self.blocks))
+      && !in_blocks(&self.exclude_blocks)
+  }
+}
+
+fn read_optional<T: Default>(
+  path: PathBuf,
+  read: impl FnOnce(&Path) -> io::Result<T>,
+) -> io::Result<T> {
+  if path.exists() {
+    read(&path)
+  } else {
+    Ok(T::default())
+  }
+}
+
+/// Writes the characters file from the UCD files. Returns the number of characters written.
+pub fn gen_data(opts: &GenOptions) -> io::Result<usize> {
+  let dir = &opts.ucd_dir;
+  let records = read_unicode_data(dir.join(UNICODE_DATA_FILE))?;
+  let names = read_optional(dir.join(DERIVED_NAME_FILE), |p| {
+    read_derived_names(p, opts.ideographs)
+  })?;
+  let blocks = read_optional(dir.join(BLOCKS_FILE), |p| read_ranges(p))?;
+  let scripts = read_optional(dir.join(SCRIPTS_FILE), |p| read_ranges(p))?;
+  let ages = read_optional(dir.join(AGE_FILE), |p| read_ranges(p))?;
+  let mut aliases = read_optional(dir.join(NAME_ALIASES_FILE), |p| read_name_aliases(p))?;
+  let informal_aliases = read_optional(dir.join(NAMES_LIST_FILE), |p| read_informal_aliases(p))?;
+  for (cp, list) in informal_aliases {
+    let merged = aliases.entry(cp).or_default();
+    for alias in list {
+      if !merged.iter().any(|a| a.eq_ignore_ascii_case(&alias)) {
+        merged.push(alias);
+      }
+    }
+  }
+
+  // Code points that share a single UnicodeData record, like CJK ideographs
+  let mut range_gcs = vec![];
+  let mut range_start = None;
+  for r in &records {
+    if r.name.ends_with(", First>") {
+      range_start = Some(r.cp);
+    } else if r.name.ends_with(", Last>") {
+      if let Some(first) = range_start.take() {
+        range_gcs.push(RangeValue {
+          first,
+          last: r.cp,
+          value: r.gc.clone(),
+        });
+      }
+    }
+  }
+
+  let mut entries: Vec<(u32, &str, &str)> = vec![];
+  for r in &records {
+    if r.name.ends_with(", First>") || r.name.ends_with(", Last>") {
+      continue;
+    }
+    if let Some(name) = names.get(&r.cp) {
+      entries.push((r.cp, name, &r.gc));
+    } else if !names.is_empty() || r.name.starts_with('<') {
+      continue;
+    } else {
+      entries.push((r.cp, &r.name, &r.gc));
+    }
+  }
+  // Names for these come from DerivedName.txt, which only names ideographs when asked to
+  for range in &range_gcs {
+    for cp in range.first..=range.last {
+      if let Some(name) = names.get(&cp) {
+        entries.push((cp, name, &range.value));
+      }
+    }
+  }
+  entries.sort_by_key(|e| e.0);
+
+  let mut out = BufWriter::new(File::create(&opts.out)?);
+  let mut count = 0;
+  for (cp, name, gc) in entries {
+    let block = range_value(&blocks, cp);
+    if !opts.includes(cp, block) {
+      continue;
+    }
+    let c = match char::from_u32(cp) {
+      Some(c) => c,
+      None => continue,
+    };
+    let chars = crate::search::escape_chars(&c.to_string());
+    write!(out, "{} {} ; cp=U+{:04X} ; gc={}", chars, name, cp, gc)?;
+    if let Some(block) = block {
+      write!(out, " ; blk={}", block)?;
+    }
+    if let Some(script) = range_value(&scripts, cp) {
+      write!(out, " ; sc={}", script)?;
+    }
+    if let Some(age) = range_value(&ages, cp) {
+      write!(out, " ; age={}", age)?;
+    }
+    if let Some(list) = aliases.get(&cp) {
+      write!(out, " ; alias={}", list.join(", "))?;
+    }
+    writeln!(out)?;
+    count += 1;
+  }
+
+  for file in EMOJI_SEQUENCE_FILES {
+    for seq in read_optional(dir.join(file), |p| read_sequences(p))? {
+      // Filters that match any of the code points apply to the whole sequence
+      let included = seq
+        .cps
+        .iter()
+        .any(|&cp| opts.includes(cp, range_value(&blocks, cp)));
+      if !included {
+        continue;
+      }
+      let chars: Option<String> = seq.cps.iter().map(|&cp| char::from_u32(cp)).collect();
+      let chars = match chars {
+        Some(chars) => chars,
+        None => continue,
+      };
+      let cps: Vec<String> = seq.cps.iter().map(|cp| format!("U+{:04X}", cp)).collect();
+      let chars = crate::search::escape_chars(&chars);
+      writeln!(out, "{} {} ; cp={}", chars, seq.name, cps.join(" "))?;
+      count += 1;
+    }
+  }
+  out.flush()?;
+  Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_code_point_ranges() {
+    assert_eq!(parse_cp_range("0370..03FF"), Some((0x370, 0x3FF)));
+    assert_eq!(parse_cp_range("U+0370..U+03FF"), Some((0x370, 0x3FF)));
+    assert_eq!(parse_cp_range("u+2192"), Some((0x2192, 0x2192)));
+    assert_eq!(parse_cp_range("U+0370..x"), None);
+  }
+
+  #[test]
+  fn finds_category_codes_in_any_case() {
+    assert_eq!(category_code("lu"), Some("Lu"));
+    assert_eq!(category_code("L"), Some("L"));
+    assert_eq!(category_code("zs"), Some("Zs"));
+    assert_eq!(category_code("letter"), None);
+  }
+
+  #[test]
+  fn range_option_accepts_u_plus_on_both_ends() {
+    let args = vec!["--range".to_string(), "U+0370..U+03FF".to_string()];
+    let opts = GenOptions::from_args(&args).unwrap();
+    assert_eq!(opts.ranges, vec![(0x370, 0x3FF)]);
+  }
+}
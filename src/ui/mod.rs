@@ -1,23 +1,41 @@
 mod gridview;
 
 use crate::caret;
+use crate::entities;
+use crate::favorites::Favorites;
+use crate::history::History;
+use crate::inspect;
+use crate::query;
+use crate::relations::{Relation, Relations};
 use crate::search;
+use crate::ucd;
 use druid::im;
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
 use druid::widget::{self, Align, Controller, Flex, Label, Padding, Scroll, TextBox};
 use druid::{
-  self, AppLauncher, Color, Data, Env, Lens, PlatformError, Selector, Widget, WidgetExt, WindowDesc,
+  self, AppLauncher, Color, Data, Env, Lens, PlatformError, RenderContext, Selector, Widget,
+  WidgetExt, WindowDesc,
 };
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, BoostQuery, Occur};
 use winapi::shared::minwindef;
 use winapi::shared::windef;
 use winapi::um::winuser;
 
 #[derive(Clone, Data)]
 struct SearchResult {
-  ch: char,
+  chars: String,
   selected: usize,
+  /// The plain name, as written to the favorites file
+  name: String,
   desc: String,
+  favorite: bool,
 }
 
 type SearchResults = im::Vector<SearchResult>;
@@ -26,8 +44,15 @@ type SearchResults = im::Vector<SearchResult>;
 pub struct AppState {
   search: Arc<String>,
   results: SearchResults,
-  search_engine: Option<search::SearchEngine>,
   select_idx: Rc<usize>,
+  history: Arc<Mutex<History>>,
+  favorites: Arc<Mutex<Favorites>>,
+  /// Number of characters that match the search, including any not loaded yet
+  total_hits: usize,
+  /// Where the next page of results starts
+  next_offset: usize,
+  /// Why the search text could not be read as a query
+  query_error: Option<String>,
 }
 
 impl AppState {
@@ -36,30 +61,365 @@ impl AppState {
   }
 }
 
-fn get_results(eng: &search::SearchEngine, s: &str, si: usize) -> tantivy::Result<SearchResults> {
+/// How much a character's past use raises its rank
+const HISTORY_WEIGHT: f32 = 0.5;
+
+fn usage_boost(usage: f32) -> f32 {
+  1. + HISTORY_WEIGHT * usage.ln_1p()
+}
+
+fn doc_name<'a>(eng: &search::SearchEngine, doc: &'a tantivy::Document) -> &'a str {
+  doc.get_first(eng.name_field).unwrap().text().unwrap()
+}
+
+/// The name of a result, with the keyword or alias that matched the query,
+/// its name in the first locale, any HTML entities and any compose sequences
+fn describe(eng: &search::SearchEngine, doc: &tantivy::Document, s: &str) -> String {
+  let name = doc_name(eng, doc);
+  let aliases = doc
+    .get_all(eng.user_keywords_field)
+    .into_iter()
+    .chain(doc.get_all(eng.alias_field))
+    .flat_map(|v| v.text());
+  let mut desc = match search::matched_alias(s, name, aliases) {
+    Some(alias) => format!("{} ({})", name, alias),
+    None => name.to_string(),
+  };
+  // Only the first locale, so that the description stays readable
+  if let Some((_locale, field)) = eng.locale_fields.first() {
+    if let Some(local_name) = doc.get_first(*field).and_then(|v| v.text()) {
+      desc = format!("{} · {}", desc, local_name);
+    }
+  }
+  for entity in doc
+    .get_all(eng.entity_field)
+    .into_iter()
+    .flat_map(|v| v.text())
+  {
+    desc = format!("{} &{};", desc, entity);
+  }
+  let sequences: Vec<&str> = doc
+    .get_all(eng.compose_field)
+    .into_iter()
+    .flat_map(|v| v.text())
+    .collect();
+  if !sequences.is_empty() {
+    desc = format!("{} (compose {})", desc, sequences.join(", "));
+  }
+  desc
+}
+
+/// Number of search hits loaded at a time
+const PAGE_SIZE: usize = 200;
+
+struct Page {
+  results: SearchResults,
+  /// Number of documents that match the query
+  total_hits: usize,
+  /// Where the next page of hits starts
+  next_offset: usize,
+  query_error: Option<String>,
+}
+
+/// Name and general category of each character, from the index or else the UCD files
+fn char_properties(
+  eng: &search::SearchEngine,
+  searcher: &tantivy::Searcher,
+  chars: impl Iterator<Item = char>,
+) -> tantivy::Result<HashMap<char, (String, String)>> {
+  let mut props = HashMap::new();
+  let mut missing = vec![];
+  for c in chars {
+    if props.contains_key(&c) || missing.contains(&(c as u32)) {
+      continue;
+    }
+    match eng.properties_of(searcher, c)? {
+      Some(p) => {
+        props.insert(c, p);
+      }
+      None => missing.push(c as u32),
+    }
+  }
+  // Characters such as ASCII that are not in the characters file
+  for (cp, p) in ucd::lookup_properties(&missing) {
+    props.extend(char::from_u32(cp).map(|c| (c, p)));
+  }
+  Ok(props)
+}
+
+/// Names of the characters, joined for a sequence
+fn chars_name(props: &HashMap<char, (String, String)>, chars: &str) -> String {
+  chars
+    .chars()
+    .map(|c| match props.get(&c) {
+      Some((name, _gc)) => name.as_str(),
+      None => "unnamed",
+    })
+    .collect::<Vec<_>>()
+    .join(" + ")
+}
+
+fn code_points(chars: &str) -> String {
+  chars
+    .chars()
+    .map(|c| format!("U+{:04X}", c as u32))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// What the results are looked up in
+struct SearchContext<'a> {
+  eng: &'a search::SearchEngine,
+  relations: &'a Relations,
+  history: &'a History,
+  favorites: &'a Favorites,
+}
+
+/// Lists the target, which may be written as a code point, then the characters
+/// related to it, as for `confusable:U+0441`
+fn related_results(
+  ctx: &SearchContext,
+  relation: Relation,
+  target: &str,
+  si: usize,
+) -> tantivy::Result<Page> {
+  let (eng, favorites) = (ctx.eng, ctx.favorites);
   let searcher = eng.reader.searcher();
-  let query = eng.query_parser.parse_query(s)?;
-  let top_docs = searcher.search(&query, &tantivy::collector::TopDocs::with_limit(400))?;
+  let target = match search::parse_code_point(target) {
+    Some(cpq) if cpq.explicit => cpq.ch.to_string(),
+    _ => target.to_string(),
+  };
+  let related = ctx.relations.related(relation, &target);
+  let listed = || std::iter::once(&target).chain(&related);
+  let props = char_properties(eng, &searcher, listed().flat_map(|chars| chars.chars()))?;
+  let mut cs = vec![];
+  if !target.is_empty() {
+    for chars in listed() {
+      let name = chars_name(&props, chars);
+      cs.push(SearchResult {
+        chars: chars.to_string(),
+        selected: si,
+        desc: format!("{} ({})", name, code_points(chars)),
+        name,
+        favorite: favorites.contains(chars),
+      });
+    }
+  }
+  let query_error = if related.is_empty() && !target.is_empty() {
+    Some(format!(
+      "nothing is listed for {}{}",
+      relation.prefix(),
+      target
+    ))
+  } else {
+    None
+  };
+  Ok(Page {
+    total_hits: cs.len(),
+    next_offset: cs.len(),
+    results: im::Vector::from(cs),
+    query_error,
+  })
+}
+
+/// Lists each grapheme of the text, described code point by code point
+fn inspect_results(ctx: &SearchContext, s: &str, si: usize) -> tantivy::Result<Page> {
+  let (eng, favorites) = (ctx.eng, ctx.favorites);
+  let searcher = eng.reader.searcher();
+  let props = char_properties(eng, &searcher, s.chars())?;
+  let cs: Vec<SearchResult> = inspect::graphemes(s)
+    .map(|g| {
+      let desc = g
+        .chars()
+        .map(|c| match props.get(&c) {
+          Some((name, gc)) => inspect::describe_char(c, name, gc),
+          None => inspect::describe_char(c, "unnamed", ""),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ");
+      SearchResult {
+        chars: g.to_string(),
+        selected: si,
+        name: chars_name(&props, g),
+        desc,
+        favorite: favorites.contains(g),
+      }
+    })
+    .collect();
+  Ok(Page {
+    total_hits: cs.len(),
+    next_offset: cs.len(),
+    results: im::Vector::from(cs),
+    query_error: None,
+  })
+}
+
+/// Gets the page of results starting at the hit `offset`. The first page
+/// also has the characters that match the query exactly.
+fn get_results(
+  ctx: &SearchContext,
+  s: &str,
+  si: usize,
+  offset: usize,
+  cancelled: &dyn Fn() -> bool,
+) -> tantivy::Result<Page> {
+  if let Some((relation, target)) = Relation::parse(s) {
+    let target = if offset == 0 { target } else { "" };
+    return related_results(ctx, relation, target, si);
+  }
+  if inspect::should_inspect(s) {
+    let text = if offset == 0 { s } else { "" };
+    return inspect_results(ctx, text, si);
+  }
+  let (eng, history, favorites) = (ctx.eng, ctx.history, ctx.favorites);
 
+  let searcher = eng.reader.searcher();
   let mut cs = vec![];
-  for (_score, doc_addr) in top_docs {
-    let doc = searcher.doc(doc_addr)?;
-    let c = doc
-      .get_first(eng.char_field)
-      .unwrap()
-      .text()
-      .unwrap()
-      .chars()
-      .nth(0)
-      .unwrap();
-    let name = doc.get_first(eng.name_field).unwrap().text().unwrap();
+
+  let code_point = search::parse_code_point(s).filter(|_| offset == 0);
+  if let Some(cpq) = &code_point {
+    let name = match eng.name_of(&searcher, cpq.ch)? {
+      Some(name) => name,
+      None => ucd::lookup_name(cpq.ch as u32).unwrap_or_default(),
+    };
+    let chars = cpq.ch.to_string();
     cs.push(SearchResult {
-      ch: c,
+      favorite: favorites.contains(&chars),
+      chars,
       selected: si,
-      desc: name.to_string(),
+      desc: name.clone(),
+      name,
     });
   }
-  Ok(im::Vector::from(cs))
+  if code_point.map_or(false, |cpq| cpq.explicit) {
+    return Ok(Page {
+      total_hits: cs.len(),
+      next_offset: cs.len(),
+      results: im::Vector::from(cs),
+      query_error: None,
+    });
+  }
+
+  let exact_fields = if offset == 0 {
+    &eng.exact_fields[..]
+  } else {
+    &[]
+  };
+  for &(label, field) in exact_fields {
+    let typed = if field == eng.entity_field {
+      entities::entity_name(s)
+    } else {
+      s.trim()
+    };
+    for doc in eng.exact_matches(&searcher, field, typed)? {
+      let chars = doc.get_first(eng.char_field).unwrap().text().unwrap();
+      if cs.iter().any(|r| r.chars == chars) {
+        continue;
+      }
+      let mut desc = describe(eng, &doc, s);
+      // Entities and compose sequences are always part of the description
+      if field == eng.digraph_field {
+        desc = format!("{} ({} {})", desc, label, typed);
+      }
+      cs.push(SearchResult {
+        chars: chars.to_string(),
+        selected: si,
+        name: doc_name(eng, &doc).to_string(),
+        desc,
+        favorite: favorites.contains(chars),
+      });
+    }
+  }
+
+  // Still show results while a query is half typed
+  let (query, query_error) = match query::build_query(eng, s) {
+    Ok(query) => (query, None),
+    Err(e) => (
+      query::build_lenient_query(eng, s)?,
+      Some(query::error_message(&e)),
+    ),
+  };
+  // Documents are only fetched for the hits on this page
+  let (total_hits, top_docs) = searcher.search(
+    &*query,
+    &(
+      tantivy::collector::Count,
+      TopDocs::with_limit(offset + PAGE_SIZE),
+    ),
+  )?;
+  let next_offset = top_docs.len();
+  // Text that matches nothing, such as a lone `Ι` or words in a script that
+  // has no names in the index, is inspected instead
+  if offset == 0 && total_hits == 0 && cs.is_empty() && !s.is_ascii() {
+    return inspect_results(ctx, s, si);
+  }
+
+  let usage = history.scores();
+  // Favorites and used characters that match are all ranked on the first
+  // page, wherever the plain score would put them, and left out of later pages
+  let boosted: HashSet<String> = favorites
+    .chars()
+    .chain(usage.keys().map(|chars| chars.to_string()))
+    .collect();
+  let mut hits = vec![];
+  if offset == 0 && !boosted.is_empty() {
+    let boosted_query = BooleanQuery::new(vec![
+      (Occur::Must, query.box_clone()),
+      (
+        Occur::Must,
+        Box::new(BoostQuery::new(
+          Box::new(eng.chars_query(boosted.iter().map(String::as_str))),
+          0.,
+        )),
+      ),
+    ]);
+    let boosted_docs = searcher.search(&boosted_query, &TopDocs::with_limit(boosted.len()))?;
+    hits.extend(
+      boosted_docs
+        .into_iter()
+        .map(|(score, addr)| (score, addr, true)),
+    );
+  }
+  hits.extend(
+    top_docs
+      .into_iter()
+      .skip(offset)
+      .map(|(score, addr)| (score, addr, false)),
+  );
+
+  let mut ranked = vec![];
+  for (score, doc_addr, is_boosted) in hits {
+    if cancelled() {
+      break;
+    }
+    let doc = searcher.doc(doc_addr)?;
+    let chars = doc.get_first(eng.char_field).unwrap().text().unwrap();
+    if cs.iter().any(|r| r.chars == chars) || (!is_boosted && boosted.contains(chars)) {
+      continue;
+    }
+    let desc = describe(eng, &doc, s);
+    let score = score * usage_boost(usage.get(chars).copied().unwrap_or(0.));
+    ranked.push((
+      score,
+      SearchResult {
+        chars: chars.to_string(),
+        selected: si,
+        name: doc_name(eng, &doc).to_string(),
+        desc,
+        favorite: favorites.contains(chars),
+      },
+    ));
+  }
+  ranked.sort_by(|(a_score, a), (b_score, b)| {
+    (b.favorite.cmp(&a.favorite)).then(b_score.partial_cmp(a_score).unwrap_or(Ordering::Equal))
+  });
+  cs.extend(ranked.into_iter().map(|(_score, r)| r));
+  Ok(Page {
+    results: im::Vector::from(cs),
+    total_hits,
+    next_offset,
+    query_error,
+  })
 }
 
 enum Direction {
@@ -67,13 +427,131 @@ enum Direction {
   Right,
   Up,
   Down,
+  PageUp,
+  PageDown,
 }
 
+/// Rows that PageUp and PageDown move the selection by
+const PAGE_ROWS: usize = 4;
+
 const CMD_SEARCH: Selector = Selector::new("search");
+const CMD_SEARCH_RESULTS: Selector<SearchResponse> = Selector::new("search-results");
+const CMD_LOAD_MORE: Selector = Selector::new("load-more-results");
+const CMD_INSERT: Selector<String> = Selector::new("insert-chars");
+const CMD_TOGGLE_FAVORITE: Selector<String> = Selector::new("toggle-favorite");
 const CMD_MOVE_SELECTION: Selector<Direction> = Selector::new("move-char-sel");
 
 const CHAR_GRID_ID: druid::WidgetId = druid::WidgetId::reserved(1);
-struct SearchController;
+fn insert_chars(history: &Mutex<History>, chars: &str) {
+  let _ = kblock::send_text_input(chars);
+  let _ = history.lock().unwrap().record(chars);
+}
+
+fn toggle_favorite(data: &mut AppState, chars: &str) {
+  let name = data
+    .results
+    .iter()
+    .find(|r| r.chars == chars)
+    .map(|r| r.name.clone())
+    .unwrap_or_default();
+  let favorite = match data.favorites.lock().unwrap().toggle(chars, &name) {
+    Ok(favorite) => favorite,
+    Err(_) => return,
+  };
+  for r in data.results.iter_mut() {
+    if r.chars == chars {
+      r.favorite = favorite;
+    }
+  }
+}
+
+/// Searches for the characters related to the selected one
+fn show_related(data: &mut AppState, relation: Relation) {
+  if let Some(r) = data.results.get(*data.select_idx) {
+    data.search = Arc::new(format!("{}{}", relation.prefix(), r.chars));
+  }
+}
+
+struct SearchRequest {
+  generation: u64,
+  text: String,
+  offset: usize,
+}
+
+struct SearchResponse {
+  generation: u64,
+  offset: usize,
+  page: tantivy::Result<Page>,
+}
+
+/// Builds the search engine, then searches in the background so that typing
+/// is never held up. Requests that have been overtaken by a newer one are skipped.
+fn run_search_worker(
+  requests: mpsc::Receiver<SearchRequest>,
+  latest: Arc<AtomicU64>,
+  sink: druid::ExtEventSink,
+  history: Arc<Mutex<History>>,
+  favorites: Arc<Mutex<Favorites>>,
+) {
+  let eng = search::new_query_parser();
+  let relations = Relations::load();
+  while let Ok(mut request) = requests.recv() {
+    while let Ok(newer) = requests.try_recv() {
+      request = newer;
+    }
+    let cancelled = || latest.load(atomic::Ordering::SeqCst) != request.generation;
+    if cancelled() {
+      continue;
+    }
+    let page = match &eng {
+      Ok(eng) => {
+        // Copies, so that typing a character does not wait for the search
+        let history = history.lock().unwrap().clone();
+        let favorites = favorites.lock().unwrap().clone();
+        let ctx = SearchContext {
+          eng,
+          relations: &relations,
+          history: &history,
+          favorites: &favorites,
+        };
+        let mut page = get_results(&ctx, &request.text, 0, request.offset, &cancelled);
+        // A broken override file is reported until the query has its own error
+        if let Ok(page) = &mut page {
+          if page.query_error.is_none() && !eng.load_errors.is_empty() {
+            page.query_error = Some(eng.load_errors.join("; "));
+          }
+        }
+        page
+      }
+      Err(e) => Err(tantivy::TantivyError::InvalidArgument(format!(
+        "the search index could not be built: {}",
+        query::error_message(e)
+      ))),
+    };
+    if cancelled() {
+      continue;
+    }
+    let response = SearchResponse {
+      generation: request.generation,
+      offset: request.offset,
+      page,
+    };
+    if sink
+      .submit_command(CMD_SEARCH_RESULTS, Box::new(response), druid::Target::Auto)
+      .is_err()
+    {
+      break;
+    }
+  }
+}
+
+struct SearchController {
+  requests: mpsc::Sender<SearchRequest>,
+  /// Generation of the most recent search, counting up with every change to the search text
+  latest: Arc<AtomicU64>,
+  /// Offset of the page that is being loaded
+  loading: Option<usize>,
+}
 fn ctrl_only(mods: &druid::Modifiers) -> bool {
   mods.ctrl() && !(mods.alt() || mods.meta() || mods.shift())
 }
@@ -89,18 +567,71 @@ impl<W: Widget<AppState>> Controller<AppState, W> for SearchController {
     match event {
       druid::Event::WindowConnected => ctx.request_focus(),
       druid::Event::Command(cmd) if cmd.is(CMD_SEARCH) => {
-        if let Some(se) = &data.search_engine {
-          let init_si = 0;
-          match get_results(se, &data.search, init_si) {
-            Ok(results) => {
-              data.set_results(results);
-              data.select_idx = Rc::new(init_si);
-              ctx.request_paint();
+        let generation = self.latest.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+        self.loading = Some(0);
+        let _ = self.requests.send(SearchRequest {
+          generation,
+          text: data.search.to_string(),
+          offset: 0,
+        });
+      }
+      druid::Event::Command(cmd) if cmd.is(CMD_LOAD_MORE) => {
+        if self.loading.is_none() && data.next_offset < data.total_hits {
+          self.loading = Some(data.next_offset);
+          let _ = self.requests.send(SearchRequest {
+            generation: self.latest.load(atomic::Ordering::SeqCst),
+            text: data.search.to_string(),
+            offset: data.next_offset,
+          });
+        }
+      }
+      druid::Event::Command(cmd) if cmd.is(CMD_SEARCH_RESULTS) => {
+        let response = cmd.get_unchecked(CMD_SEARCH_RESULTS);
+        let current = response.generation == self.latest.load(atomic::Ordering::SeqCst);
+        if current && self.loading == Some(response.offset) {
+          self.loading = None;
+          match &response.page {
+            Ok(page) if response.offset > 0 => {
+              let si = *data.select_idx;
+              for r in page.results.iter() {
+                if !data.results.iter().any(|loaded| loaded.chars == r.chars) {
+                  data.results.push_back(SearchResult {
+                    selected: si,
+                    ..r.clone()
+                  });
+                }
+              }
+              data.next_offset = page.next_offset;
+              data.query_error = page.query_error.clone();
+            }
+            Ok(page) => {
+              data.set_results(page.results.clone());
+              data.total_hits = page.total_hits;
+              data.next_offset = page.next_offset;
+              data.query_error = page.query_error.clone();
+              data.select_idx = Rc::new(0);
+            }
+            Err(e) if response.offset > 0 => {
+              // The pages already loaded stay, and scrolling asks for this one again
+              data.query_error = Some(query::error_message(e));
+            }
+            Err(e) => {
+              data.set_results(im::vector![]);
+              data.total_hits = 0;
+              data.next_offset = 0;
+              data.query_error = Some(query::error_message(e));
+              data.select_idx = Rc::new(0);
             }
-            Err(_) => println!("error getting results"),
           }
+          ctx.request_paint();
         }
       }
+      druid::Event::Command(cmd) if cmd.is(CMD_INSERT) => {
+        insert_chars(&data.history, cmd.get_unchecked(CMD_INSERT));
+      }
+      druid::Event::Command(cmd) if cmd.is(CMD_TOGGLE_FAVORITE) => {
+        toggle_favorite(data, cmd.get_unchecked(CMD_TOGGLE_FAVORITE));
+      }
       druid::Event::KeyDown(druid::KeyEvent { mods, key, .. }) => {
         use druid_shell::keyboard_types::Key;
         if let Key::Character(s) = key {
@@ -115,6 +646,32 @@ impl<W: Widget<AppState>> Controller<AppState, W> for SearchController {
               }
               None
             }
+            "s" => {
+              let idx = *data.select_idx;
+              if ctrl_only(mods) && idx < data.results.len() {
+                let chars = data.results[idx].chars.clone();
+                toggle_favorite(data, &chars);
+              }
+              None
+            }
+            "e" => {
+              if ctrl_only(mods) {
+                show_related(data, Relation::Confusable);
+              }
+              None
+            }
+            "d" => {
+              if ctrl_only(mods) {
+                show_related(data, Relation::Variant);
+              }
+              None
+            }
+            "r" => {
+              if ctrl_only(mods) {
+                show_related(data, Relation::Related);
+              }
+              None
+            }
             _ => None,
           };
           if let Some(move_dir) = move_dir {
@@ -122,13 +679,18 @@ impl<W: Widget<AppState>> Controller<AppState, W> for SearchController {
               ctx.submit_command(CMD_MOVE_SELECTION.with(move_dir).to(CHAR_GRID_ID));
             }
           }
+        } else if let Key::PageUp | Key::PageDown = key {
+          let dir = if let Key::PageUp = key {
+            Direction::PageUp
+          } else {
+            Direction::PageDown
+          };
+          ctx.submit_command(CMD_MOVE_SELECTION.with(dir).to(CHAR_GRID_ID));
         } else if let Key::Enter = key {
           let idx = *data.select_idx as usize;
           let results = &data.results;
           if idx < results.len() {
-            let s = results[idx].ch.to_string();
-            let s = s.as_str();
-            let _ = kblock::send_text_input(s);
+            insert_chars(&data.history, &results[idx].chars);
           }
         }
       }
@@ -190,6 +752,8 @@ impl Controller<GridViewState<SearchResults>, gridview::GridView<SearchResult>>
                     }
                   }
                   Direction::Down => (data.items.len() - 1).min(old_idx + cols),
+                  Direction::PageUp => old_idx.saturating_sub(cols * PAGE_ROWS),
+                  Direction::PageDown => (data.items.len() - 1).min(old_idx + cols * PAGE_ROWS),
                   _ => 0, // unreachable
                 }
               }
@@ -199,6 +763,9 @@ impl Controller<GridViewState<SearchResults>, gridview::GridView<SearchResult>>
             }
             data.x = new_idx;
             ctx.request_paint();
+            if new_idx + child.ncolumns() * PAGE_ROWS >= data.items.len() {
+              ctx.submit_command(CMD_LOAD_MORE);
+            }
           }
         }
       }
@@ -208,6 +775,73 @@ impl Controller<GridViewState<SearchResults>, gridview::GridView<SearchResult>>
   }
 }
 
+/// Loads more results when the grid is scrolled near its end
+struct ScrollController;
+impl<W: Widget<AppState>> Controller<AppState, Scroll<AppState, W>> for ScrollController {
+  fn event(
+    &mut self,
+    child: &mut Scroll<AppState, W>,
+    ctx: &mut druid::EventCtx,
+    event: &druid::Event,
+    data: &mut AppState,
+    env: &Env,
+  ) {
+    child.event(ctx, event, data, env);
+    if let druid::Event::Wheel(_) | druid::Event::MouseMove(_) = event {
+      let bottom = child.offset().y + ctx.size().height;
+      if bottom >= child.child_size().height - ctx.size().height {
+        ctx.submit_command(CMD_LOAD_MORE);
+      }
+    }
+  }
+}
+
+/// Types the character when clicked, or pins it as a favorite if Ctrl is held
+struct CellController;
+impl<W: Widget<SearchResult>> Controller<SearchResult, W> for CellController {
+  fn event(
+    &mut self,
+    child: &mut W,
+    ctx: &mut druid::EventCtx,
+    event: &druid::Event,
+    data: &mut SearchResult,
+    env: &Env,
+  ) {
+    match event {
+      druid::Event::MouseDown(_) => ctx.set_active(true),
+      druid::Event::MouseUp(mouse) if ctx.is_active() => {
+        ctx.set_active(false);
+        if ctx.is_hot() {
+          let cmd = if mouse.mods.ctrl() {
+            CMD_TOGGLE_FAVORITE
+          } else {
+            CMD_INSERT
+          };
+          ctx.submit_command(cmd.with(data.chars.clone()));
+        }
+      }
+      _ => (),
+    }
+    child.event(ctx, event, data, env)
+  }
+}
+
+fn paint_cell_background(ctx: &mut druid::PaintCtx, data: &SearchResult, _env: &Env) {
+  let bounds = ctx.size().to_rect();
+  ctx.fill(bounds, &Color::BLACK);
+  if data.favorite {
+    let star = ctx
+      .text()
+      .new_text_layout("★")
+      .font(druid::FontFamily::SYSTEM_UI, 9.)
+      .text_color(Color::YELLOW)
+      .build()
+      .unwrap();
+    let x = bounds.width() - star.size().width - 3.;
+    ctx.draw_text(&star, (x, 1.));
+  }
+}
+
 struct CharGridLens;
 
 use crate::ui::gridview::GridViewState;
@@ -235,7 +869,7 @@ impl Lens<AppState, GridViewState<SearchResults>> for CharGridLens {
   }
 }
 
-fn build_root_widget() -> impl Widget<AppState> {
+fn build_root_widget(search_controller: SearchController) -> impl Widget<AppState> {
   const FONT: druid::FontDescriptor =
     druid::FontDescriptor::new(druid::FontFamily::SYSTEM_UI).with_size(18.0);
 
@@ -249,10 +883,21 @@ fn build_root_widget() -> impl Widget<AppState> {
             .with_placeholder("Single")
             .lens(AppState::search)
             .expand_width()
-            .controller(SearchController),
+            .controller(search_controller),
           1.,
         )
         .with_spacer(5.)
+        .with_child(
+          Label::new(
+            |data: &AppState, _env: &_| match data.total_hits.max(data.results.len()) {
+              0 => "".to_string(),
+              1 => "1 result".to_string(),
+              n => format!("{} results", n),
+            },
+          )
+          .with_text_size(11.),
+        )
+        .with_spacer(5.)
         .with_child(widget::Button::new("⨯").on_click(|_ctx, _data, _env| {
           druid::Application::global().quit();
         }))
@@ -262,25 +907,22 @@ fn build_root_widget() -> impl Widget<AppState> {
       Scroll::new(Padding::new(
         druid::Insets::new(3., 0., 8., 0.),
         gridview::GridView::new(|data: &SearchResult, grid_ctx: gridview::GridViewItemCtx| {
-          Align::centered(Label::new(|r: &SearchResult, _env: &_| r.ch.to_string()).with_font(FONT))
-            .on_click(
-              |_ctx: &mut druid::EventCtx<'_, '_>, data: &mut SearchResult, _env: &Env| {
-                let s = &data.ch.to_string();
-                let _ = kblock::send_text_input(s);
-              },
-            )
-            .background(widget::BackgroundBrush::Color(Color::BLACK))
-            .border(
-              {
-                if grid_ctx.index == data.selected {
-                  Color::YELLOW
-                } else {
-                  Color::rgb(0.16, 0.16, 0.16)
-                }
-              },
-              1.,
-            )
-            .rounded(7.)
+          Align::centered(
+            Label::new(|r: &SearchResult, _env: &_| inspect::visible(&r.chars)).with_font(FONT),
+          )
+          .controller(CellController)
+          .background(widget::Painter::new(paint_cell_background))
+          .border(
+            {
+              if grid_ctx.index == data.selected {
+                Color::YELLOW
+              } else {
+                Color::rgb(0.16, 0.16, 0.16)
+              }
+            },
+            1.,
+          )
+          .rounded(7.)
         })
         .with_spacing(0.)
         .with_item_size(druid::Size {
@@ -291,15 +933,21 @@ fn build_root_widget() -> impl Widget<AppState> {
         .with_id(CHAR_GRID_ID)
         .lens(CharGridLens),
       ))
-      .vertical(),
+      .vertical()
+      .controller(ScrollController),
       1.,
     )
     .with_child(
       Label::new(|data: &AppState, _env: &_| {
-        if *data.select_idx < data.results.len() {
+        let desc = if *data.select_idx < data.results.len() {
           data.results[*data.select_idx].desc.clone()
         } else {
           "".to_string()
+        };
+        match &data.query_error {
+          Some(error) if desc.is_empty() => format!("⚠ {}", error),
+          Some(error) => format!("⚠ {} · {}", error, desc),
+          None => desc,
         }
       })
       .with_text_size(11.)
@@ -332,7 +980,13 @@ pub fn main() -> Result<(), PlatformError> {
     width: 212.,
     height: 131.,
   };
-  let root = build_root_widget();
+  let (requests, request_receiver) = mpsc::channel();
+  let latest = Arc::new(AtomicU64::new(0));
+  let root = build_root_widget(SearchController {
+    requests,
+    latest: latest.clone(),
+    loading: None,
+  });
   let dw_ex_style: minwindef::DWORD =
     winuser::WS_EX_NOACTIVATE | winuser::WS_EX_OVERLAPPEDWINDOW | winuser::WS_EX_TOPMOST;
   let native_config = druid::NativeWindowConfig::default().set_dwExStyle(dw_ex_style);
@@ -355,11 +1009,20 @@ pub fn main() -> Result<(), PlatformError> {
   let initial_state = AppState {
     search: "".to_string().into(),
     results: im::vector![],
-    search_engine: search::new_query_parser().ok(),
     select_idx: 0.into(),
+    history: Arc::new(Mutex::new(History::load())),
+    favorites: Arc::new(Mutex::new(Favorites::load())),
+    total_hits: 0,
+    next_offset: 0,
+    query_error: None,
   };
 
-  AppLauncher::with_window(window).launch(initial_state)?;
+  let launcher = AppLauncher::with_window(window);
+  let sink = launcher.get_external_handle();
+  let history = initial_state.history.clone();
+  let favorites = initial_state.favorites.clone();
+  thread::spawn(move || run_search_worker(request_receiver, latest, sink, history, favorites));
+  launcher.launch(initial_state)?;
 
   drop(block_hook);
 
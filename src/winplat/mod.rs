@@ -3,6 +3,7 @@ pub mod keyboard;
 
 use std::mem;
 use windows::Win32::Foundation::{BOOL, HWND};
+use windows::Win32::System::Console;
 use windows::Win32::UI::WindowsAndMessaging as wam;
 
 /// Required to recieve callbacks to hooks that are not on a GUI thread
@@ -19,3 +20,9 @@ pub fn pump_messages() -> bool {
     msg.message != wam::WM_QUIT
   }
 }
+
+/// Lets a command-line invocation print to the terminal it was started from,
+/// since the program is built for the windows subsystem
+pub fn attach_console() -> bool {
+  unsafe { Console::AttachConsole(Console::ATTACH_PARENT_PROCESS).as_bool() }
+}